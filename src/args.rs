@@ -1,4 +1,5 @@
 use clap::Parser;
+use std::path::PathBuf;
 
 /// A lightweight and flexible command-line JSON, TOML processor and converter.
 #[derive(Parser, Debug)]
@@ -8,6 +9,10 @@ pub struct Args {
     #[clap(short, long, value_parser)]
     pub input: Option<String>,
 
+    /// Input file, standard input is read when neither a file nor --input is given
+    #[clap(value_parser)]
+    pub file: Option<PathBuf>,
+
     /// JSON format filter
     #[clap(long, value_parser)]
     pub filter: String,
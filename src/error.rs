@@ -0,0 +1,325 @@
+use serde_json::Value;
+use std::{fmt, ops::Range};
+
+/// Byte range into the filter or input document an error refers to.
+pub type Span = Range<usize>;
+
+/// Position in an input document a decode error refers to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub snippet: String,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The input file or standard input could not be read.
+    Io(std::io::Error),
+    /// `--from` or `--to` named a language ruq does not support.
+    UnsupportedLanguage(String),
+    /// The filter could not be tokenized or parsed.
+    Syntax {
+        message: String,
+        span: Span,
+        hint: Option<String>,
+    },
+    /// The filter was applied to a value of the wrong type.
+    Type(String),
+    /// The filter raised an error with `error/1`.
+    Raised(Value),
+    /// The input document could not be decoded.
+    Decode {
+        language: String,
+        message: String,
+        location: Option<Location>,
+    },
+    /// The result could not be encoded in the output language.
+    Encode { language: String, message: String },
+}
+
+impl Error {
+    /// Process exit code reported for this error.
+    ///
+    /// | Code | Meaning                                   |
+    /// |------|-------------------------------------------|
+    /// | 2    | Usage error or unreadable input           |
+    /// | 3    | Filter syntax error                       |
+    /// | 4    | Input document could not be decoded       |
+    /// | 5    | Filter failed while running               |
+    /// | 6    | Result could not be encoded               |
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Io(_) | Error::UnsupportedLanguage(_) => 2,
+            Error::Syntax { .. } => 3,
+            Error::Decode { .. } => 4,
+            Error::Type(_) | Error::Raised(_) => 5,
+            Error::Encode { .. } => 6,
+        }
+    }
+
+    pub fn syntax(message: impl Into<String>, span: Span) -> Self {
+        Error::Syntax {
+            message: message.into(),
+            span,
+            hint: None,
+        }
+    }
+
+    /// Decode error for `source`, located at the one-based `line` and
+    /// `column` reported by the underlying parser.
+    pub fn decode(
+        language: &str,
+        message: impl fmt::Display,
+        source: &str,
+        position: Option<(usize, usize)>,
+    ) -> Self {
+        let mut message = message.to_string();
+        if let Some(index) = message.rfind(" at line ") {
+            message.truncate(index);
+        }
+
+        let location = position.map(|(line, column)| {
+            let line_start = source
+                .split_inclusive('\n')
+                .take(line.saturating_sub(1))
+                .map(str::len)
+                .sum::<usize>();
+            let offset = source[line_start..]
+                .char_indices()
+                .nth(column.saturating_sub(1))
+                .map_or(source.len(), |(i, _)| line_start + i);
+
+            Location {
+                file: String::from("<stdin>"),
+                line,
+                column: column.max(1),
+                snippet: snippet(source, &(offset..offset + 1), None),
+            }
+        });
+
+        Error::Decode {
+            language: language.to_string(),
+            message,
+            location,
+        }
+    }
+
+    /// Names the file a decode or read error was found in.
+    pub fn in_file(mut self, name: &str) -> Self {
+        match &mut self {
+            Error::Decode {
+                location: Some(location),
+                ..
+            } => location.file = name.to_string(),
+            Error::Io(e) => *e = std::io::Error::new(e.kind(), format!("{}: {}", name, e)),
+            _ => {}
+        }
+
+        self
+    }
+
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        if let Error::Syntax { hint: h, .. } = &mut self {
+            *h = Some(hint.into());
+        }
+
+        self
+    }
+
+    /// The value `try ... catch` hands to its handler: the message of a
+    /// runtime error or the value given to `error/1`.
+    pub fn into_value(self) -> Value {
+        match self {
+            Error::Raised(value) => value,
+            e => Value::String(e.to_string()),
+        }
+    }
+
+    /// Renders the error for the terminal. Syntax errors point a caret at the
+    /// offending part of `filter`.
+    pub fn report(&self, filter: &str) -> String {
+        match self {
+            Error::Syntax {
+                message,
+                span,
+                hint,
+            } => {
+                let (line, column) = line_column(filter, span.start);
+                format!(
+                    "ruq: error: invalid filter: {} at line {}, column {}\n{}",
+                    message,
+                    line,
+                    column,
+                    snippet(filter, span, hint.as_deref())
+                )
+            }
+            Error::Decode {
+                location: Some(location),
+                ..
+            } => format!("ruq: error: {}\n{}", self, location.snippet),
+            e => format!("ruq: error: {}", e),
+        }
+    }
+}
+
+/// One-based line and column of the byte `offset` in `source`.
+pub fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let offset = offset.min(source.len());
+    let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = source[..offset].matches('\n').count() + 1;
+    let column = source[line_start..offset].chars().count() + 1;
+
+    (line, column)
+}
+
+/// Quotes the line of `source` containing `span` with carets underneath it.
+pub fn snippet(source: &str, span: &Span, label: Option<&str>) -> String {
+    let start = span.start.min(source.len());
+    let (line, column) = line_column(source, start);
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |i| start + i);
+    let text = &source[line_start..line_end];
+
+    let end = span.end.clamp(start, line_end);
+    let carets = source[start..end].chars().count().max(1);
+    let gutter = " ".repeat(line.to_string().len());
+
+    let mut snippet = format!(
+        "{} |\n{} | {}\n{} | {}{}",
+        gutter,
+        line,
+        text,
+        gutter,
+        " ".repeat(column - 1),
+        "^".repeat(carets)
+    );
+    if let Some(label) = label {
+        snippet = format!("{} {}", snippet, label);
+    }
+
+    snippet
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "cannot read input: {}", e),
+            Error::UnsupportedLanguage(language) => {
+                write!(f, "unsupported language {:?}", language)
+            }
+            Error::Syntax { message, span, .. } => {
+                write!(f, "invalid filter: {} at byte {}", message, span.start)
+            }
+            Error::Type(message) => write!(f, "{}", message),
+            Error::Raised(Value::String(message)) => write!(f, "{}", message),
+            Error::Raised(value) => write!(f, "{} (not a string)", value),
+            Error::Decode {
+                language,
+                message,
+                location: None,
+            } => write!(f, "cannot decode {} input: {}", language, message),
+            Error::Decode {
+                message,
+                location: Some(location),
+                ..
+            } => write!(
+                f,
+                "{}:{}:{}: {}",
+                location.file, location.line, location.column, message
+            ),
+            Error::Encode { language, message } => {
+                write!(f, "cannot encode result as {}: {}", language, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+mod test_error {
+    #[test]
+    fn report_syntax_errors() {
+        use super::*;
+
+        let error = Error::syntax("unexpected end of filter", 9..9).with_hint("expected ']'");
+        assert_eq!(
+            error.report(".foo[1 + "),
+            [
+                "ruq: error: invalid filter: unexpected end of filter at line 1, column 10",
+                "  |",
+                "1 | .foo[1 + ",
+                "  |          ^ expected ']'",
+            ]
+            .join("\n")
+        );
+
+        let error = Error::syntax("unexpected ')'", 12..13);
+        assert_eq!(
+            error.report(".a |\n  .b | )"),
+            [
+                "ruq: error: invalid filter: unexpected ')' at line 2, column 8",
+                "  |",
+                "2 |   .b | )",
+                "  |        ^",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn report_decode_errors() {
+        use super::*;
+
+        let source = "a: 1\nb:\n  c: d: e\n";
+        let error = Error::decode(
+            "YAML",
+            "mapping values are not allowed in this context at line 3 column 7",
+            source,
+            Some((3, 7)),
+        )
+        .in_file("deploy.yaml");
+        assert_eq!(
+            error.report(""),
+            [
+                "ruq: error: deploy.yaml:3:7: mapping values are not allowed in this context",
+                "  |",
+                "3 |   c: d: e",
+                "  |       ^",
+            ]
+            .join("\n")
+        );
+
+        let error = Error::decode("JSON", "EOF while parsing", "{\n", Some((2, 0)));
+        assert_eq!(
+            error.report(""),
+            [
+                "ruq: error: <stdin>:2:1: EOF while parsing",
+                "  |",
+                "2 | ",
+                "  | ^",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn report_read_errors() {
+        use super::*;
+
+        let error = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+        assert_eq!(
+            Error::from(error).in_file("deploy.yaml").report(""),
+            "ruq: error: cannot read input: deploy.yaml: no such file"
+        );
+    }
+}
@@ -1,52 +1,104 @@
-mod args;
-mod parser;
-mod processor;
-mod toml;
-mod yaml;
-
-use args::{Args, SupportedLanguages};
-use clap::Parser;
-use processor::Processor;
-use serde_json::Value;
-use std::{
-    io::{stdin, stdout, Read, Write},
-    str::FromStr,
-};
-
-fn main() {
-    let args = Args::parse();
-    let input = match args.input {
-        Some(e) => e,
-        None => {
-            let mut buffer = String::new();
-            stdin().read_to_string(&mut buffer).unwrap();
-            buffer
-        }
-    };
-
-    let json = match SupportedLanguages::from(args.from.clone()) {
-        SupportedLanguages::Json => Value::from_str(input.as_str()).unwrap(),
-        SupportedLanguages::Toml => toml::Toml::new(input).to_json(),
-        SupportedLanguages::Yaml => yaml::Yaml::new(input).to_json(),
-        SupportedLanguages::Unsupported => panic!("Unsupported language"),
-    };
-
-    let result = parser::Parser::parse(json, &args.filter);
-
-    let conversion_to = {
-        if let Some(to_value) = args.to {
-            to_value
-        } else {
-            args.from
-        }
-    };
-
-    let result = match SupportedLanguages::from(conversion_to) {
-        SupportedLanguages::Json => serde_json::to_string_pretty(&result).unwrap(),
-        SupportedLanguages::Toml => toml::Toml::from_json(result).to_string(),
-        SupportedLanguages::Yaml => yaml::Yaml::from_json(result).to_string(),
-        SupportedLanguages::Unsupported => panic!("Unsupported language"),
-    };
-
-    stdout().write_all(result.as_bytes()).unwrap();
-}
+mod args;
+mod error;
+mod parser;
+mod processor;
+mod toml;
+mod yaml;
+
+pub use error::Error;
+
+use args::{Args, SupportedLanguages};
+use clap::Parser;
+use processor::Processor;
+use serde_json::Value;
+use std::{
+    fs,
+    io::{stdin, stdout, Read, Write},
+    process,
+    str::FromStr,
+};
+
+fn main() {
+    let args = Args::parse();
+
+    let filter = args.filter.clone();
+
+    if let Err(e) = run(args) {
+        eprintln!("{}", e.report(&filter));
+        process::exit(e.exit_code());
+    }
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    let parser = parser::Parser::new(&args.filter)?;
+
+    let (name, input) = match (args.input, &args.file) {
+        (Some(e), _) => (String::from("<input>"), e),
+        (None, Some(file)) => {
+            let name = file.display().to_string();
+            let input = fs::read_to_string(file).map_err(|e| Error::from(e).in_file(&name))?;
+            (name, input)
+        }
+        (None, None) => {
+            let mut buffer = String::new();
+            stdin().read_to_string(&mut buffer)?;
+            (String::from("<stdin>"), buffer)
+        }
+    };
+
+    let json = match SupportedLanguages::from(args.from.clone()) {
+        SupportedLanguages::Json => Value::from_str(input.as_str()).map_err(|e| {
+            let position = Some((e.line(), e.column())).filter(|(line, _)| *line > 0);
+            Error::decode("JSON", &e, &input, position)
+        }),
+        SupportedLanguages::Toml => toml::Toml::new(input).and_then(|e| e.to_json()),
+        SupportedLanguages::Yaml => yaml::Yaml::new(input).and_then(|e| e.to_json()),
+        SupportedLanguages::Unsupported => return Err(Error::UnsupportedLanguage(args.from)),
+    }
+    .map_err(|e| e.in_file(&name))?;
+
+    let conversion_to = {
+        if let Some(to_value) = args.to {
+            to_value
+        } else {
+            args.from
+        }
+    };
+
+    let language = SupportedLanguages::from(conversion_to.clone());
+    if let SupportedLanguages::Unsupported = language {
+        return Err(Error::UnsupportedLanguage(conversion_to));
+    }
+
+    let mut stdout = stdout().lock();
+    for (index, result) in parser.run(json).enumerate() {
+        let result = match language {
+            SupportedLanguages::Json => {
+                let result = toml::unwrap_datetimes(result?);
+                let mut json =
+                    serde_json::to_string_pretty(&result).map_err(|e| Error::Encode {
+                        language: String::from("JSON"),
+                        message: e.to_string(),
+                    })?;
+                json.push('\n');
+                json
+            }
+            SupportedLanguages::Toml => toml::Toml::from_json(result?)?.to_string()?,
+            SupportedLanguages::Yaml => {
+                yaml::Yaml::from_json(toml::unwrap_datetimes(result?))?.to_string()?
+            }
+            SupportedLanguages::Unsupported => unreachable!(),
+        };
+
+        // Separate outputs so each one stays a valid document on its own.
+        match language {
+            SupportedLanguages::Toml if index > 0 => stdout.write_all(b"\n")?,
+            SupportedLanguages::Yaml if index > 0 => stdout.write_all(b"---\n")?,
+            _ => {}
+        }
+
+        stdout.write_all(result.as_bytes())?;
+    }
+
+    Ok(())
+}
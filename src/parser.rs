@@ -1,559 +1,36 @@
-use regex::Regex;
-use serde_json::{Map, Number, Value};
-use std::str::FromStr;
-
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub enum Parser {
-    Json(Value),
-    Length,
-    Operator(Vec<(Operator, Value)>),
-}
-
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub enum Operator {
-    Addition,
-    Subtration,
-    Multiplication,
-    Division { ignore_infinite_divisor: bool },
-    Modulo { ignore_infinite_divisor: bool },
-    Nil,
-}
-
-struct JsonParser {
-    json: Value,
-    filter_regex: Regex,
-}
-
-impl JsonParser {
-    fn new(json: Value) -> Self {
-        let filter_regex = Regex::new(r"(\.(?P<key>\w*)\s*(\[(?P<index>\d+?)\])?)").unwrap();
-        Self { json, filter_regex }
-    }
-
-    fn parse_json(&self, query: String) -> Value {
-        let parsed_query = self.parse_filters(query);
-        Value::from_str(&parsed_query).unwrap()
-    }
-
-    fn parse_filters(&self, mut query: String) -> String {
-        let chars = query.clone();
-        let chars = chars.chars();
-
-        let mut discard = false;
-        let mut found_filter = false;
-        let mut filter_index = 0;
-        let mut filter_range = vec![];
-
-        for (index, char) in chars.into_iter().enumerate() {
-            match char {
-                '"' | '\'' | '\\' => {
-                    discard = !discard;
-                }
-                '.' => {
-                    if discard || found_filter {
-                        continue;
-                    }
-
-                    found_filter = true;
-                    filter_index = index;
-                }
-                '}' | ']' | ',' | ' ' => {
-                    if found_filter {
-                        let left_char = query.chars().nth(index - 1).unwrap();
-
-                        if left_char == '[' || left_char.is_numeric() {
-                            if query.chars().nth(index + 1).is_some() {
-                                continue;
-                            }
-
-                            filter_range.push(filter_index..=index);
-                        } else {
-                            filter_range.push(filter_index..=index - 1);
-                        }
-
-                        discard = false;
-                        found_filter = false;
-                    }
-                }
-                _ => {}
-            }
-
-            if query.chars().nth(index + 1).is_none() && found_filter {
-                filter_range.push(filter_index..=index);
-            }
-        }
-
-        // Loop backwards
-        while let Some(range) = filter_range.pop() {
-            let start = *range.start();
-
-            let mut filter: String = query.drain(range).collect();
-            if self.filter_regex.is_match(&filter) {
-                let mut value = self.json.clone();
-                for filter_capture in self.filter_regex.captures_iter(&filter) {
-                    let key = filter_capture.name("key").unwrap().as_str();
-                    if !key.is_empty() {
-                        value = value.get(key).cloned().unwrap_or_default();
-                    }
-
-                    if let Some(e) = filter_capture.name("index") {
-                        value = value
-                            .get(e.as_str().parse::<usize>().unwrap())
-                            .cloned()
-                            .unwrap_or_default()
-                    }
-                }
-
-                filter = value.to_string()
-            } else {
-                panic!("Invalid filter {}", filter)
-            }
-
-            query.insert_str(start, filter.as_str());
-        }
-
-        query
-    }
-
-    pub fn json_data_operator(mut json: Vec<(Operator, Value)>) -> Value {
-        let (mut recent_operator, mut recent_value) = json.first().unwrap().clone();
-
-        for (operator, value) in json.drain(1..) {
-            let value = match recent_operator {
-                Operator::Addition => Self::add_json_data(recent_value, value),
-                Operator::Subtration => Self::subtract_json_data(recent_value, value),
-                Operator::Multiplication => Self::multiply_json_data(recent_value, value),
-                Operator::Division {
-                    ignore_infinite_divisor,
-                } => Self::divide_json_data(recent_value, value, ignore_infinite_divisor),
-                Operator::Modulo {
-                    ignore_infinite_divisor,
-                } => Self::modulo_json_data(recent_value, value, ignore_infinite_divisor),
-                Operator::Nil => unreachable!(),
-            };
-
-            recent_operator = operator;
-            recent_value = value;
-        }
-
-        recent_value
-    }
-
-    fn add_json_data(pre: Value, post: Value) -> Value {
-        let pre_type_id = pre.to_string();
-        let post_type_id = post.to_string();
-        match (pre, post) {
-            (Value::Array(e), Value::Array(f)) => [e, f].concat().into(),
-            (Value::Object(mut e), Value::Object(mut f)) => {
-                e.append(&mut f);
-                e.into()
-            }
-            (Value::Number(a), Value::Number(e)) => {
-                let value = a.as_f64().unwrap() + e.as_f64().unwrap();
-                value.into()
-            }
-            (Value::String(a), Value::String(e)) => [a, e].concat().into(),
-            (Value::Number(e), Value::Null) | (Value::Null, Value::Number(e)) => e.into(),
-            _ => panic!("{:?} and {:?} cannot be added", pre_type_id, post_type_id),
-        }
-    }
-
-    fn subtract_json_data(pre: Value, post: Value) -> Value {
-        let pre_type_id = pre.to_string();
-        let post_type_id = post.to_string();
-        match (pre, post) {
-            (Value::Array(e), Value::Array(f)) => {
-                let mut result = vec![];
-
-                for value in e {
-                    if !f.contains(&value) {
-                        result.push(value)
-                    }
-                }
-
-                result.into()
-            }
-            (Value::Number(a), Value::Number(e)) => {
-                let value = a.as_f64().unwrap() - e.as_f64().unwrap();
-                value.into()
-            }
-            _ => panic!(
-                "{:?} and {:?} cannot be subtracted",
-                pre_type_id, post_type_id
-            ),
-        }
-    }
-
-    fn multiply_json_data(pre: Value, post: Value) -> Value {
-        let pre_type_id = pre.to_string();
-        let post_type_id = post.to_string();
-        match (pre, post) {
-            (Value::Object(a), Value::Object(mut e)) => {
-                let mut result = Map::new();
-
-                for (key, pre_value) in a {
-                    if let Some(post_value) = e.get(key.as_str()) {
-                        result.insert(
-                            key.clone(),
-                            Self::multiply_json_data(pre_value, post_value.clone()),
-                        );
-                        e.remove(&key);
-                    } else {
-                        result.insert(key, pre_value);
-                    }
-                }
-
-                result.extend(e);
-                result.into()
-            }
-            (Value::Number(a), Value::Number(e)) => {
-                let value = a.as_f64().unwrap() * e.as_f64().unwrap();
-                value.into()
-            }
-            (Value::String(mut e), Value::Number(a)) | (Value::Number(a), Value::String(mut e)) => {
-                let a = a.as_u64().unwrap();
-                if a == 0 {
-                    return Value::Null;
-                }
-
-                for _ in 0..(a - 1) {
-                    e += e.clone().as_str();
-                }
-                e.into()
-            }
-            _ => panic!(
-                "{:?} and {:?} cannot be multiplied",
-                pre_type_id, post_type_id
-            ),
-        }
-    }
-
-    fn divide_json_data(pre: Value, post: Value, ignore_infinite_divisor: bool) -> Value {
-        let pre_type_id = pre.to_string();
-        let post_type_id = post.to_string();
-
-        match (pre, post) {
-            (Value::String(e), Value::String(a)) => {
-                let value: Vec<_> = e.split(&a).collect();
-                value.into()
-            }
-            (Value::Number(e), Value::Array(a)) => {
-                let mut result = vec![];
-                let e = Self::convert_to_f64(e);
-
-                for value in a {
-                    match value {
-                        Value::Number(a) => {
-                            let a = Self::convert_to_f64(a);
-
-                            if a == 0.0 {
-                                if !ignore_infinite_divisor {
-                                    panic!(
-                                        "{:?} and {:?} cannot be divided",
-                                        pre_type_id, post_type_id
-                                    )
-                                }
-
-                                continue;
-                            }
-
-                            result.push(e / a)
-                        }
-                        _ => panic!("{:?} and {:?} cannot be divided", pre_type_id, post_type_id),
-                    }
-                }
-
-                result.into()
-            }
-            (Value::Array(e), Value::Number(a)) => {
-                let mut result = vec![];
-                let a = Self::convert_to_f64(a);
-                if a == 0.0 {
-                    if !ignore_infinite_divisor {
-                        panic!("{:?} and {:?} cannot be divided", pre_type_id, post_type_id)
-                    }
-
-                    return Value::Null;
-                }
-
-                for value in e {
-                    match value {
-                        Value::Number(e) => {
-                            let e = Self::convert_to_f64(e);
-
-                            result.push(e / a)
-                        }
-                        _ => panic!("{:?} and {:?} cannot be divided", pre_type_id, post_type_id),
-                    }
-                }
-
-                result.into()
-            }
-            (Value::Number(e), Value::Number(a)) => {
-                let e = Self::convert_to_f64(e);
-                let a = Self::convert_to_f64(a);
-
-                if a == 0.0 {
-                    panic!("{:?} and {:?} cannot be divided", pre_type_id, post_type_id);
-                }
-
-                (e / a).into()
-            }
-            _ => panic!("{:?} and {:?} cannot be divided", pre_type_id, post_type_id),
-        }
-    }
-
-    fn convert_to_f64(value: Number) -> f64 {
-        if value.is_f64() {
-            value.as_f64().unwrap()
-        } else if value.is_i64() {
-            value.as_i64().unwrap() as f64
-        } else if value.is_u64() {
-            value.as_u64().unwrap() as f64
-        } else {
-            0.0
-        }
-    }
-
-    fn modulo_json_data(pre: Value, post: Value, ignore_infinite_divisor: bool) -> Value {
-        let pre_type_id = pre.to_string();
-        let post_type_id = post.to_string();
-
-        match (pre, post) {
-            (Value::Number(e), Value::Array(a)) => {
-                let mut result = vec![];
-                let e = Self::convert_to_f64(e);
-
-                for value in a {
-                    match value {
-                        Value::Number(a) => {
-                            let a = Self::convert_to_f64(a);
-
-                            if a == 0.0 {
-                                if !ignore_infinite_divisor {
-                                    panic!(
-                                        "Cannot compute {:?} and {:?} modulo",
-                                        pre_type_id, post_type_id
-                                    )
-                                }
-
-                                continue;
-                            }
-
-                            result.push(e % a)
-                        }
-                        _ => panic!(
-                            "Cannot compute {:?} and {:?} modulo",
-                            pre_type_id, post_type_id
-                        ),
-                    }
-                }
-
-                result.into()
-            }
-            (Value::Array(e), Value::Number(a)) => {
-                let mut result = vec![];
-                let a = Self::convert_to_f64(a);
-                if a == 0.0 {
-                    if !ignore_infinite_divisor {
-                        panic!(
-                            "Cannot compute {:?} and {:?} modulo",
-                            pre_type_id, post_type_id
-                        )
-                    }
-
-                    return Value::Null;
-                }
-
-                for value in e {
-                    match value {
-                        Value::Number(e) => {
-                            let e = Self::convert_to_f64(e);
-
-                            result.push(e % a)
-                        }
-                        _ => panic!(
-                            "Cannot compute {:?} and {:?} modulo",
-                            pre_type_id, post_type_id
-                        ),
-                    }
-                }
-
-                result.into()
-            }
-            (Value::Number(e), Value::Number(a)) => {
-                let e = Self::convert_to_f64(e);
-                let a = Self::convert_to_f64(a);
-
-                if a == 0.0 {
-                    panic!(
-                        "Cannot compute {:?} and {:?} modulo",
-                        pre_type_id, post_type_id
-                    );
-                }
-
-                (e % a).into()
-            }
-            _ => panic!(
-                "Cannot compute {:?} and {:?} modulo",
-                pre_type_id, post_type_id
-            ),
-        }
-    }
+mod ast;
+mod builtin;
+mod date;
+mod evaluator;
+mod format;
+mod lexer;
+mod operator;
+mod path;
+
+use crate::Error;
+use ast::Ast;
+use evaluator::{Env, Evaluator, Outputs};
+use serde_json::Value;
+
+pub struct Parser {
+    ast: Ast,
 }
 
 impl Parser {
-    pub fn parse(json_data: Value, data: &str) -> Value {
-        let parser = Regex::new(r"\s*(?P<pre>.*?)\s*\|\s*(?P<post>.*)\s*").unwrap();
-
-        let (mut pre, mut post) = Self::regexer(&parser, data);
-
-        let mut value = Self::query(json_data, pre.to_string());
-
-        while post != None {
-            (pre, post) = Self::regexer(&parser, post.unwrap());
-            value = Self::query(value, pre.to_string());
-        }
-
-        value
-    }
-
-    fn query(json_data: Value, query: String) -> Value {
-        match Parser::parse_pipe(json_data.clone(), query) {
-            Parser::Json(e) => e,
-            Parser::Length => Self::get_json_length(&json_data).into(),
-            Parser::Operator(e) => JsonParser::json_data_operator(e),
-        }
+    pub fn new(filter: &str) -> Result<Self, Error> {
+        Ok(Self {
+            ast: Ast::parse(filter)?,
+        })
     }
 
-    fn get_json_length(json_data: &Value) -> f64 {
-        if let Some(e) = json_data.as_array() {
-            return e.len() as f64;
-        } else if let Some(e) = json_data.as_object() {
-            return e.len() as f64;
-        } else if let Some(e) = json_data.as_str() {
-            return e.len() as f64;
-        } else if let Some(e) = json_data.as_f64() {
-            return e;
-        } else if let Some(e) = json_data.as_i64() {
-            return e as f64;
-        } else if let Some(e) = json_data.as_u64() {
-            return e as f64;
-        }
-
-        panic!("Cannot get length of type")
-    }
-
-    fn regexer<'a>(parser: &Regex, data: &'a str) -> (&'a str, Option<&'a str>) {
-        if parser.is_match(data) {
-            let capture = parser.captures(data).unwrap();
-
-            let pre = capture.name("pre").unwrap().as_str();
-            let post = capture.name("post").map(|a| a.as_str());
-            return (pre, post);
-        }
-
-        (data, None)
-    }
-
-    fn parse_pipe(json_data: Value, data: String) -> Self {
-        let json_parser = JsonParser::new(json_data);
-
-        let length_compatibily = Regex::new(r"length").unwrap();
-        let operator_compatibily =
-            Regex::new(r"\s*(?P<pre>.*?)\s*(?P<operator>\+|\*|/|%|-)\s*(?P<post>.*)\s*").unwrap();
-
-        if length_compatibily.is_match(&data) {
-            return Parser::Length;
-        } else if operator_compatibily.is_match(&data) {
-            let mut operators = vec![];
-
-            let mut data = data;
-            let mut ignore_infinite_divisor_ = false;
-            loop {
-                if !operator_compatibily.is_match(&data) {
-                    if !data.is_empty() {
-                        let value = json_parser.parse_json(data);
-                        operators.push((Operator::Nil, value));
-                    }
-
-                    return Parser::Operator(operators);
-                }
-
-                let (pre, post, operator) = {
-                    if let Some(capture) = operator_compatibily.captures(&data) {
-                        let mut operator = {
-                            if let Some(op) = capture.name("operator") {
-                                Operator::from(op.as_str())
-                            } else {
-                                Operator::Nil
-                            }
-                        };
-
-                        let mut pre = {
-                            if let Some(op) = capture.name("pre") {
-                                op.as_str()
-                            } else {
-                                ""
-                            }
-                        };
-
-                        let mut post = {
-                            if let Some(op) = capture.name("post") {
-                                op.as_str()
-                            } else {
-                                ""
-                            }
-                        };
-
-                        if pre.starts_with('(') && post.ends_with(")?") {
-                            ignore_infinite_divisor_ = true;
-                            pre = pre.strip_prefix('(').unwrap();
-                            post = post.strip_suffix(")?").unwrap();
-                        }
-
-                        match &mut operator {
-                            Operator::Division {
-                                ignore_infinite_divisor,
-                            } => *ignore_infinite_divisor = ignore_infinite_divisor_,
-                            Operator::Modulo {
-                                ignore_infinite_divisor,
-                            } => *ignore_infinite_divisor = ignore_infinite_divisor_,
-                            _ => {}
-                        }
-
-                        let pre = json_parser.parse_json(pre.to_owned());
-
-                        (pre, post, operator)
-                    } else {
-                        (Value::Null, "", Operator::Nil)
-                    }
-                };
-
-                operators.push((operator, pre));
-
-                data = post.to_string();
-            }
-        }
-
-        Parser::Json(json_parser.parse_json(data))
+    /// Runs the filter against `json_data`, yielding each of its outputs.
+    pub fn run(&self, json_data: Value) -> Outputs<'_> {
+        Evaluator::eval(&self.ast, &Env::default(), json_data)
     }
-}
 
-impl From<&str> for Operator {
-    fn from(val: &str) -> Self {
-        match val {
-            "+" => Self::Addition,
-            "-" => Self::Subtration,
-            "/" => Self::Division {
-                ignore_infinite_divisor: false,
-            },
-            "*" => Self::Multiplication,
-            "%" => Self::Modulo {
-                ignore_infinite_divisor: false,
-            },
-            _ => Self::Nil,
-        }
+    #[allow(dead_code)]
+    pub fn parse(json_data: Value, data: &str) -> Result<Vec<Value>, Error> {
+        Self::new(data)?.run(json_data).collect()
     }
 }
 
@@ -566,41 +43,51 @@ mod test_parser {
 
         struct TestParser {
             query: String,
-            result: Value,
+            results: Vec<Value>,
             json: Value,
         }
 
         let tests = [
             TestParser {
                 query: String::from(".[]"),
-                result: Value::from_str(r#"[1,0,-1]"#).unwrap(),
+                results: vec![
+                    serde_json::json!(1),
+                    serde_json::json!(0),
+                    serde_json::json!(-1),
+                ],
                 json: Value::from_str(r#"[1,0,-1]"#).unwrap(),
             },
             TestParser {
                 query: String::from(".[].[0]"),
-                result: Value::from_str(r#"1"#).unwrap(),
-                json: Value::from_str(r#"[1,0,-1]"#).unwrap(),
+                results: vec![
+                    serde_json::json!(1),
+                    serde_json::json!(0),
+                    serde_json::json!(-1),
+                ],
+                json: Value::from_str(r#"[[1],[0],[-1]]"#).unwrap(),
             },
             TestParser {
                 query: String::from("."),
-                result: Value::from_str(r#"{}"#).unwrap(),
+                results: vec![Value::from_str(r#"{}"#).unwrap()],
                 json: Value::from_str(r#"{}"#).unwrap(),
             },
             TestParser {
                 query: String::from(" ."),
-                result: Value::from_str(r#"{"a": 1}"#).unwrap(),
+                results: vec![Value::from_str(r#"{"a": 1}"#).unwrap()],
                 json: Value::from_str(r#"{"a": 1}"#).unwrap(),
             },
             TestParser {
                 query: String::from(r#" . | {"michael_age": .a, "michael_height": .a}"#),
-                result: Value::from_str(r#"{"michael_age": 1, "michael_height": 1}"#).unwrap(),
+                results: vec![
+                    Value::from_str(r#"{"michael_age": 1, "michael_height": 1}"#).unwrap(),
+                ],
                 json: Value::from_str(r#"{"a": 1}"#).unwrap(),
             },
         ];
 
         for (i, test) in tests.into_iter().enumerate() {
-            let parsed = Parser::parse(test.json.clone(), &test.query);
-            assert_eq!(parsed, test.result, "Failed testing index {}", i);
+            let parsed = Parser::parse(test.json.clone(), &test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
         }
     }
 
@@ -611,36 +98,46 @@ mod test_parser {
 
         struct TestParser {
             query: String,
-            result: Value,
+            results: Vec<Value>,
             json: Value,
         }
 
         let tests = [
             TestParser {
                 query: String::from(".a | length"),
-                result: serde_json::json!(1.0),
+                results: vec![serde_json::json!(1)],
                 json: Value::from_str(r#"{"a": 1}"#).unwrap(),
             },
             TestParser {
                 query: String::from(" .a|length"),
-                result: serde_json::json!(2.0),
+                results: vec![serde_json::json!(2)],
                 json: Value::from_str(r#"{"a": [{"a": 1}, {"b": 2}]}"#).unwrap(),
             },
             TestParser {
                 query: String::from(" .a[0]|length"),
-                result: serde_json::json!(2.0),
+                results: vec![serde_json::json!(2)],
                 json: Value::from_str(r#"{"a": [{"a": 55, "c": 100}, {"b": 2}]}"#).unwrap(),
             },
             TestParser {
                 query: String::from(" .a[0].c.d|length"),
-                result: serde_json::json!(100.0),
+                results: vec![serde_json::json!(100)],
                 json: Value::from_str(r#"{"a": [{"a": 55, "c": { "d": 100}}, {"b": 2}]}"#).unwrap(),
             },
+            TestParser {
+                query: String::from(".missing | length"),
+                results: vec![serde_json::json!(0)],
+                json: Value::from_str(r#"{"a": 1}"#).unwrap(),
+            },
+            TestParser {
+                query: String::from(".a | length"),
+                results: vec![serde_json::json!(5)],
+                json: Value::from_str(r#"{"a": -5}"#).unwrap(),
+            },
         ];
 
         for (i, test) in tests.into_iter().enumerate() {
-            let parsed = Parser::parse(test.json.clone(), &test.query);
-            assert_eq!(parsed, test.result, "Failed testing index {}", i);
+            let parsed = Parser::parse(test.json.clone(), &test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
         }
     }
 
@@ -651,18 +148,18 @@ mod test_parser {
 
         struct TestParser {
             query: String,
-            result: Value,
+            results: Vec<Value>,
             json: Value,
         }
 
         let tests = [
             TestParser {
                 query: String::from(r#". | {"a": .a} + {"b": .b} + {"c": .c} + {"a": .c}"#),
-                result: serde_json::json!({
+                results: vec![serde_json::json!({
                     "a": true,
                     "b": 1,
                     "c": true,
-                }),
+                })],
                 json: serde_json::json!({
                     "a": "Hello",
                     "b": 1,
@@ -671,11 +168,11 @@ mod test_parser {
             },
             TestParser {
                 query: String::from(r#". | {"a": .a} + {"b": {"a": .b}} + {"c": .c} + {"a": .c}"#),
-                result: serde_json::json!({
+                results: vec![serde_json::json!({
                     "a": true,
                     "b": {"a": 1},
                     "c": true,
-                }),
+                })],
                 json: serde_json::json!({
                     "a": "Hello",
                     "b": 1,
@@ -686,7 +183,7 @@ mod test_parser {
                 query: String::from(
                     r#". | {"a": .a} + {"b": .b} + {"c": .c} + {"a": .c} | .b + 1"#,
                 ),
-                result: serde_json::json!(6.0),
+                results: vec![serde_json::json!(6)],
                 json: serde_json::json!({
                     "a": "Hello",
                     "b": 5,
@@ -697,7 +194,7 @@ mod test_parser {
                 query: String::from(
                     r#". | {"a": .a} + {"b": {"a": .b}} + {"c": .c} + {"a": .c} | length"#,
                 ),
-                result: serde_json::json!(3.0),
+                results: vec![serde_json::json!(3)],
                 json: serde_json::json!({
                     "a": "Hello",
                     "b": 1,
@@ -706,171 +203,2154 @@ mod test_parser {
             },
             TestParser {
                 query: String::from(r#". | .d + 1 | length"#),
-                result: serde_json::json!(1.0),
+                results: vec![serde_json::json!(1)],
                 json: serde_json::json!({}),
             },
             TestParser {
                 query: String::from(r#"2 + .d  | length"#),
-                result: serde_json::json!(2.0),
+                results: vec![serde_json::json!(2)],
                 json: serde_json::json!({}),
             },
             TestParser {
                 query: String::from(r#""Hello" * 2"#),
-                result: serde_json::json!("HelloHello"),
+                results: vec![serde_json::json!("HelloHello")],
                 json: serde_json::json!({}),
             },
             TestParser {
                 query: String::from(r#". / ", ""#),
-                result: serde_json::json!(["a", "b,c,d", "e"]),
+                results: vec![serde_json::json!(["a", "b,c,d", "e"])],
                 json: serde_json::json!("a, b,c,d, e"),
             },
             TestParser {
                 query: String::from(r#"10 / . * 3"#),
-                result: serde_json::json!(5.0),
+                results: vec![serde_json::json!(5)],
                 json: serde_json::json!(6),
             },
             TestParser {
                 query: String::from(r#".[] | (1 / .)?"#),
-                result: serde_json::json!([1.0, -1.0]),
+                results: vec![serde_json::json!(1), serde_json::json!(-1)],
                 json: serde_json::json!([1, 0, -1]),
             },
             TestParser {
                 query: String::from(r#".[] | (1 / 1 / .)?"#),
-                result: serde_json::json!([1.0, -1.0]),
+                results: vec![serde_json::json!(1), serde_json::json!(-1)],
                 json: serde_json::json!([1, 0, -1]),
             },
             TestParser {
                 query: String::from(r#"12 % . * 3"#),
-                result: serde_json::json!(6.0),
+                results: vec![serde_json::json!(6)],
                 json: serde_json::json!(5),
             },
             TestParser {
                 query: String::from(r#".[] | (3 % .)?"#),
-                result: serde_json::json!([1.0, 1.0]),
+                results: vec![serde_json::json!(1), serde_json::json!(1)],
                 json: serde_json::json!([2, 0, -2]),
             },
             TestParser {
                 query: String::from(r#".[] | (3 % 2 / .)?"#),
-                result: serde_json::json!([1.0, -1.0]),
+                results: vec![serde_json::json!(1), serde_json::json!(-1)],
                 json: serde_json::json!([1, 0, -1]),
             },
             TestParser {
                 query: String::from(r#". | ["xml", "json"] - ["xml"]"#),
-                result: Value::from_str(r#"["json"]"#).unwrap(),
+                results: vec![Value::from_str(r#"["json"]"#).unwrap()],
                 json: serde_json::json!({}),
             },
             TestParser {
                 query: String::from(r#". | ["xml", "json"] - ["xml"]"#),
-                result: Value::from_str(r#"["json"]"#).unwrap(),
+                results: vec![Value::from_str(r#"["json"]"#).unwrap()],
                 json: serde_json::json!({}),
             },
             TestParser {
                 query: String::from(r#"[{"xml": 1}, {"yaml": 2}] - [{"xml": 1}]"#),
-                result: Value::from_str(r#"[{"yaml": 2}]"#).unwrap(),
+                results: vec![Value::from_str(r#"[{"yaml": 2}]"#).unwrap()],
                 json: serde_json::json!({}),
             },
             TestParser {
                 query: String::from(r#"[{"xml": 1}, {"yaml": 2}] - [{"xml": 2}]"#),
-                result: Value::from_str(r#"[{"xml": 1}, {"yaml": 2}]"#).unwrap(),
+                results: vec![Value::from_str(r#"[{"xml": 1}, {"yaml": 2}]"#).unwrap()],
                 json: serde_json::json!({}),
             },
             TestParser {
                 query: String::from(r#"{"k": {"a": 1, "b": 2}} * {"k": {"a": 0,"c": 3}}"#),
-                result: Value::from_str(r#"{"k": {"a": 0.0, "b": 2, "c": 3}}"#).unwrap(),
+                results: vec![Value::from_str(r#"{"k": {"a": 0, "b": 2, "c": 3}}"#).unwrap()],
                 json: serde_json::json!({}),
             },
         ];
 
         for (i, test) in tests.into_iter().enumerate() {
-            let parsed = Parser::parse(test.json.clone(), &test.query);
-            assert_eq!(parsed, test.result, "Failed testing index {}", i);
+            let parsed = Parser::parse(test.json.clone(), &test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+    }
+
+    #[test]
+    fn test_slices_and_keys() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let tests = [
+            TestParser {
+                query: ".[2:4], .[:2], .[3:], .[-2:], .[:-3], .[4:1], .[1.5:2.5]",
+                results: vec![
+                    json!([2, 3]),
+                    json!([0, 1]),
+                    json!([3, 4]),
+                    json!([3, 4]),
+                    json!([0, 1]),
+                    json!([]),
+                    json!([1, 2]),
+                ],
+                json: json!([0, 1, 2, 3, 4]),
+            },
+            TestParser {
+                query: ".[-1], .[-5], .[-6], .[1:][-1], [.[0, 2]], [.[0, 1:2]]",
+                results: vec![
+                    json!(4),
+                    json!(0),
+                    json!(null),
+                    json!(4),
+                    json!([0, 2]),
+                    json!([[0, 1], [1]]),
+                ],
+                json: json!([0, 1, 2, 3, 4]),
+            },
+            TestParser {
+                query: ".[1:3], .[-2:], .[:1], .[2:][1:]",
+                results: vec![json!("éé"), json!("vé"), json!("r"), json!("avé")],
+                json: json!("rééavé"),
+            },
+            TestParser {
+                query: ".[1:3] = [9], del(.[:2]), .[-1:] |= map(. * 2), [path(.[1:])]",
+                results: vec![
+                    json!([0, 9, 3]),
+                    json!([2, 3]),
+                    json!([0, 1, 2, 6]),
+                    json!([[{"start": 1, "end": null}]]),
+                ],
+                json: json!([0, 1, 2, 3]),
+            },
+            TestParser {
+                query: r#"."dev-dependencies", .["key with spaces"], .a."b-c", .a.["b-c"]"#,
+                results: vec![json!({"serde": "1"}), json!(2), json!(3), json!(3)],
+                json: json!({"dev-dependencies": {"serde": "1"}, "key with spaces": 2, "a": {"b-c": 3}}),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+
+        let error = Parser::parse(json!({"a": 1}), ".[:1]").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"Cannot index object ({"a":1}) with object ({"end":1,"...)"#
+        );
+
+        let error = Parser::parse(json!([1, 2]), ".[:1] = 1").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "A slice of an array can only be assigned another array"
+        );
+    }
+}
+
+mod test_precedence {
+    #[test]
+    fn test_arithmetic_precedence() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let tests = [
+            TestParser {
+                query: "1 + 2 * 3",
+                results: vec![json!(7)],
+                json: json!(null),
+            },
+            TestParser {
+                query: "(1 + 2) * 3",
+                results: vec![json!(9)],
+                json: json!(null),
+            },
+            TestParser {
+                query: "10 - 4 - 3, 2 * 3 % 4, 10 - 6 / 2",
+                results: vec![json!(3), json!(2), json!(7)],
+                json: json!(null),
+            },
+            TestParser {
+                query: "-.a, -.a * 2, - -.a, 3 - -1",
+                results: vec![json!(-5), json!(-10), json!(5), json!(4)],
+                json: json!({"a": 5}),
+            },
+            TestParser {
+                query: "((.a + 1) * (2 - (.a)))",
+                results: vec![json!(-18)],
+                json: json!({"a": 5}),
+            },
+            TestParser {
+                query: ".a + 1 > 5 and .a * 2 == 10 or false",
+                results: vec![json!(true)],
+                json: json!({"a": 5}),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
         }
+
+        let error = Parser::parse(json!("a"), "-.").unwrap_err();
+        assert_eq!(error.to_string(), r#"string ("a") cannot be negated"#);
     }
 }
 
-mod test_json_types {
+mod test_conditional {
+    #[test]
+    fn test_conditionals() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let tests = [
+            TestParser {
+                query: r#"if .env == "prod" then .replicas else 1 end"#,
+                results: vec![json!(3)],
+                json: json!({"env": "prod", "replicas": 3}),
+            },
+            TestParser {
+                query: r#"if .env == "prod" then .replicas else 1 end"#,
+                results: vec![json!(1)],
+                json: json!({"env": "dev", "replicas": 3}),
+            },
+            TestParser {
+                query: r#".[] | if . > 2 then "big" elif . > 1 then "medium" else "small" end"#,
+                results: vec![json!("small"), json!("medium"), json!("big")],
+                json: json!([1, 2, 3]),
+            },
+            TestParser {
+                query: "if .a then .b end, if 0, null then 1 else 2 end",
+                results: vec![json!({"a": false}), json!(1), json!(2)],
+                json: json!({"a": false}),
+            },
+            TestParser {
+                query: "if (true, false) then 1, 2 else 3 end",
+                results: vec![json!(1), json!(2), json!(3)],
+                json: json!(null),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+    }
 
     #[test]
-    fn find_length() {
+    fn test_alternative() {
         use super::*;
+        use serde_json::json;
+
         struct TestParser {
-            query: String,
-            json_types: Vec<Parser>,
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
         }
 
         let tests = [
             TestParser {
-                query: String::from("length"),
-                json_types: vec![Parser::Length],
+                query: ".timeout // 30",
+                results: vec![json!(30)],
+                json: json!({}),
             },
             TestParser {
-                query: String::from(" length"),
-                json_types: vec![Parser::Length],
+                query: ".timeout // 30",
+                results: vec![json!(10)],
+                json: json!({"timeout": 10}),
+            },
+            TestParser {
+                query: ".a // .b // 3, (false, null) // 4",
+                results: vec![json!(3), json!(4)],
+                json: json!({"a": false, "b": null}),
+            },
+            TestParser {
+                query: "(.[] | .port) // 80",
+                results: vec![json!(1), json!(2)],
+                json: json!([{"port": 1}, {"port": null}, {"port": 2}]),
+            },
+            TestParser {
+                query: "(1 + {}) // 1, empty // 2, (1 // 2 | . + 1)",
+                results: vec![json!(1), json!(2), json!(2)],
+                json: json!(null),
+            },
+            TestParser {
+                query: ".a // .b and false",
+                results: vec![json!(false)],
+                json: json!({"a": null}),
             },
         ];
 
-        for (i, test) in tests.iter().enumerate() {
-            let parsed = Parser::parse_pipe(Value::Null, test.query.clone());
-            assert_eq!(vec![parsed], test.json_types, "Failed testing index {}", i);
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
         }
     }
 }
 
-mod test {
+mod test_variable {
     #[test]
-    fn make_valid_json() {
-        use super::JsonParser;
-        use serde_json::Value;
-        use std::str::FromStr;
+    fn test_bindings() {
+        use super::*;
+        use serde_json::json;
 
         struct TestParser {
-            query: String,
-            result: Value,
+            query: &'static str,
+            results: Vec<Value>,
             json: Value,
         }
+
         let tests = [
             TestParser {
-                query: String::from(r#"{"a": .a}"#),
-                result: Value::from_str(r#"{"a":"Hello"}"#).unwrap(),
-                json: serde_json::json!({
-                    "a": "Hello",
-                    "b": 1,
-                    "c": true,
-                }),
+                query: ". as $root | .items[] | {name, owner: $root.meta.owner}",
+                results: vec![
+                    json!({"name": "api", "owner": "ops"}),
+                    json!({"name": "web", "owner": "ops"}),
+                ],
+                json: json!({"meta": {"owner": "ops"}, "items": [{"name": "api"}, {"name": "web"}]}),
             },
             TestParser {
-                query: String::from(r#"{"a": .a[0]}"#),
-                result: Value::from_str(r#"{"a":{"a":55,"c":100}}"#).unwrap(),
-                json: Value::from_str(
-                    r#"{"a": [{"a": 55, "c": 100}, {"b": 2}], "b": 1,"c": true}"#,
-                )
-                .unwrap(),
+                query: ".[] as $x | $x * 2",
+                results: vec![json!(2), json!(4)],
+                json: json!([1, 2]),
             },
             TestParser {
-                query: String::from(" .a[0]"),
-                result: Value::from_str(r#"{"a":55,"c":100}"#).unwrap(),
-                json: Value::from_str(r#"{"a": [{"a": 55, "c": 100}, {"b": 2}]}"#).unwrap(),
+                query: ". as $x | (.a as $x | $x), $x.a",
+                results: vec![json!(1), json!(1)],
+                json: json!({"a": 1}),
             },
             TestParser {
-                query: String::from(r#"{"a": "a.b"}"#),
-                result: Value::from_str(r#"{"a": "a.b"}"#).unwrap(),
-                json: Value::from_str(r#"{}"#).unwrap(),
+                query: "1 as $x | 2 as $y | [$x, $y, $x + $y]",
+                results: vec![json!([1, 2, 3])],
+                json: json!(null),
             },
             TestParser {
-                query: String::from(r#"{"a": ".a", "b": .a}"#),
-                result: Value::from_str(r#"{"a":".a", "b": "Hello"}"#).unwrap(),
-                json: serde_json::json!({
-                    "a": "Hello",
-                    "b": 1,
-                    "c": true,
-                }),
+                query: ". as [$a, {b: $c}] | {$a, $c}",
+                results: vec![json!({"a": 1, "c": 2})],
+                json: json!([1, {"b": 2}]),
+            },
+            TestParser {
+                query: ". as {$a, $b: [$c], (\"x\", \"y\"): $d} | [$a, $b, $c, $d]",
+                results: vec![json!([1, [2], 2, 3]), json!([1, [2], 2, 4])],
+                json: json!({"a": 1, "b": [2], "x": 3, "y": 4}),
+            },
+            TestParser {
+                query: ". as [$a, $b] | [$a, $b]",
+                results: vec![json!([1, null])],
+                json: json!([1]),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+    }
+
+    #[test]
+    fn test_destructuring_alternative() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let tests = [
+            TestParser {
+                query: ".[] as [$a] ?// $a | $a",
+                results: vec![json!(1), json!(2)],
+                json: json!([[1], 2]),
+            },
+            TestParser {
+                query: ".[] as {a: $x} ?// [$x] ?// $y | [$x, $y]",
+                results: vec![json!([1, null]), json!([2, null]), json!([null, 3])],
+                json: json!([{"a": 1}, [2], 3]),
+            },
+            TestParser {
+                query: ". as [$a] ?// $a | if ($a | length) > 1 then $a + {} else $a end",
+                results: vec![json!([[1, 2]])],
+                json: json!([[1, 2]]),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+
+        let error = Parser::parse(json!(1), ". as [$a] ?// {a: $a} | $a").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"Cannot index number (1) with string ("a")"#
+        );
+    }
+}
+
+mod test_function {
+    #[test]
+    fn test_definitions() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let tests = [
+            TestParser {
+                query: "def double: . * 2; map(double)",
+                results: vec![json!([2, 4])],
+                json: json!([1, 2]),
+            },
+            TestParser {
+                query: "def fac: if . <= 1 then 1 else . * (. - 1 | fac) end; fac",
+                results: vec![json!(120)],
+                json: json!(5),
+            },
+            TestParser {
+                query: "def addvalue(f): f as $x | map(. + $x); addvalue(.[0])",
+                results: vec![json!([[1, 2, 1, 2], [10, 1, 2]])],
+                json: json!([[1, 2], [10]]),
+            },
+            TestParser {
+                query: "def f($a; $b): [$a, $b, a]; f(1, 2; 3)",
+                results: vec![json!([1, 3, 1, 2]), json!([2, 3, 1, 2])],
+                json: json!(null),
+            },
+            TestParser {
+                query: "def f(g): [g, g]; f(.[])",
+                results: vec![json!([1, 2, 1, 2])],
+                json: json!([1, 2]),
+            },
+            TestParser {
+                query: "def f: 1; def g: f; def f: 2; [f, g]",
+                results: vec![json!([2, 1])],
+                json: json!(null),
+            },
+            TestParser {
+                query: "1 as $x | def f: $x; 2 as $x | [f, $x]",
+                results: vec![json!([1, 2])],
+                json: json!(null),
+            },
+            TestParser {
+                query: "def f(g): def h: g; [h, (2 | h)]; f(. + 1)",
+                results: vec![json!([2, 3])],
+                json: json!(1),
+            },
+            TestParser {
+                query: "def f: def f: 2; f + 1; f, (def map(f): f; map(3))",
+                results: vec![json!(3), json!(3)],
+                json: json!(null),
+            },
+            TestParser {
+                query: "def f(x): x * 2; f(f(3))",
+                results: vec![json!(12)],
+                json: json!(null),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+    }
+}
+
+mod test_iteration {
+    #[test]
+    fn test_reduce_and_foreach() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let tests = [
+            TestParser {
+                query: "reduce .[].cpu as $cpu (0; . + $cpu)",
+                results: vec![json!(3.5)],
+                json: json!([{"cpu": 1}, {"cpu": 2.5}]),
+            },
+            TestParser {
+                query: "reduce .[] as {name: $k, $port} ({}; . + {($k): $port})",
+                results: vec![json!({"api": 80, "db": 5432})],
+                json: json!([{"name": "api", "port": 80}, {"name": "db", "port": 5432}]),
+            },
+            TestParser {
+                query: "reduce .[] as [$k, $v] ({}; . + {($k): $v})",
+                results: vec![json!({"a": 1, "b": 2})],
+                json: json!([["a", 1], ["b", 2]]),
+            },
+            TestParser {
+                query: "reduce empty as $x (0, 1; . + 1), reduce .[] as $x (0; empty)",
+                results: vec![json!(0), json!(1), json!(null)],
+                json: json!([1]),
+            },
+            TestParser {
+                query: "[foreach .[] as $x (0; . + $x)]",
+                results: vec![json!([1, 3, 6])],
+                json: json!([1, 2, 3]),
+            },
+            TestParser {
+                query: "[foreach .[] as $x (0; . + $x; [$x, .])]",
+                results: vec![json!([[1, 1], [2, 3]])],
+                json: json!([1, 2]),
+            },
+            TestParser {
+                query: "[foreach .[] as $x (0; (. + $x, . - $x); .)]",
+                results: vec![json!([1, -1, 1, -3])],
+                json: json!([1, 2]),
             },
         ];
 
         for (i, test) in tests.into_iter().enumerate() {
-            let parser = JsonParser::new(test.json.clone());
-            let value = parser.parse_json(test.query.clone());
-            assert_eq!(value, test.result, "Failed testing index {}", i);
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+    }
+
+    #[test]
+    fn test_generators() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let tests = [
+            TestParser {
+                query: "[range(3)], [range(1; 3)], [range(0; 1; 0.25)], [range(3; 0; -1)]",
+                results: vec![
+                    json!([0, 1, 2]),
+                    json!([1, 2]),
+                    json!([0, 0.25, 0.5, 0.75]),
+                    json!([3, 2, 1]),
+                ],
+                json: json!(null),
+            },
+            TestParser {
+                query: "[range(0, 1; 2, 3)], [range(0; 1; 0)]",
+                results: vec![json!([0, 1, 0, 1, 2, 1, 1, 2]), json!([])],
+                json: json!(null),
+            },
+            TestParser {
+                query: "[limit(3; .[])], [limit(0; .[])], [limit(-1; .[])], [limit(2; range(100000000))]",
+                results: vec![json!([1, 2, 3]), json!([]), json!([1, 2, 3, 4]), json!([0, 1])],
+                json: json!([1, 2, 3, 4]),
+            },
+            TestParser {
+                query: "first, last, first(.[] | . * 2), last(.[] | . * 2), [first(empty)]",
+                results: vec![json!(1), json!(4), json!(2), json!(8), json!([])],
+                json: json!([1, 2, 3, 4]),
+            },
+            TestParser {
+                query: "until(. > 100; . * 2), [while(. < 20; . * 3)]",
+                results: vec![json!(128), json!([1, 3, 9])],
+                json: json!(1),
+            },
+            TestParser {
+                query: "[limit(3; foreach range(1; 1000000000) as $x (0; . + $x))]",
+                results: vec![json!([1, 3, 6])],
+                json: json!(null),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+    }
+}
+
+mod test_error_handling {
+    #[test]
+    fn test_try_catch() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let tests = [
+            TestParser {
+                query: r#"try (1 + "a") catch ."#,
+                results: vec![json!(r#"number (1) and string ("a") cannot be added"#)],
+                json: json!(null),
+            },
+            TestParser {
+                query: r#"try error("bad input") catch ., try error({code: 2}) catch .code"#,
+                results: vec![json!("bad input"), json!(2)],
+                json: json!(null),
+            },
+            TestParser {
+                query: "[.[] | try if . == 2 then error else . end]",
+                results: vec![json!([1, 3])],
+                json: json!([1, 2, 3]),
+            },
+            TestParser {
+                query: "[try (.[] | if . == 2 then error else . end) catch (-1)]",
+                results: vec![json!([1, -1])],
+                json: json!([1, 2, 3]),
+            },
+            TestParser {
+                query: "[.[] | .a?], [.[][]?], [.[] | (1 / .a)?]",
+                results: vec![json!([1]), json!([1, 1, 2]), json!([1])],
+                json: json!([{"a": 1}, [1, 2]]),
+            },
+            TestParser {
+                query: "try error catch ., [.[]?]",
+                results: vec![json!(3), json!([])],
+                json: json!(3),
+            },
+            TestParser {
+                query: "try error(null) catch ., (try error(\"x\") catch error(. + \"!\")) // 1",
+                results: vec![json!(null), json!(1)],
+                json: json!(null),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+
+        let error =
+            Parser::parse(json!(null), "try error(\"x\") catch error(. + \"!\")").unwrap_err();
+        assert_eq!(error.exit_code(), 5);
+        assert_eq!(error.to_string(), "x!");
+
+        let error = Parser::parse(json!(null), "error({a: 1})").unwrap_err();
+        assert_eq!(error.to_string(), r#"{"a":1} (not a string)"#);
+    }
+}
+
+mod test_null_propagation {
+    #[test]
+    fn test_indexing() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let tests = [
+            TestParser {
+                query: ".foo, .foo.bar, .[0], .[1:], .foo[2].bar",
+                results: vec![
+                    json!(null),
+                    json!(null),
+                    json!(null),
+                    json!(null),
+                    json!(null),
+                ],
+                json: json!(null),
+            },
+            TestParser {
+                query: ".missing, .missing.bar, .missing[0], .foo.missing",
+                results: vec![json!(null), json!(null), json!(null), json!(null)],
+                json: json!({"foo": {}}),
+            },
+            TestParser {
+                query: ".[5], .[-5], .[0].missing",
+                results: vec![json!(null), json!(null), json!(null)],
+                json: json!([{}]),
+            },
+            TestParser {
+                query: ".foo.bar?, .foo[0]?, .foo.bar?.baz, [.[]?], .foo?",
+                results: vec![json!([3]), json!(3)],
+                json: json!({"foo": 3}),
+            },
+            TestParser {
+                query: "[.[] | .a?], [.[] | .[0]?], [.[] | try .a catch \"bad\"]",
+                results: vec![
+                    json!([1, null]),
+                    json!([5, null]),
+                    json!([1, "bad", "bad", null]),
+                ],
+                json: json!([{"a": 1}, [5], "s", null]),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+    }
+
+    #[test]
+    fn test_indexing_errors() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestError {
+            query: &'static str,
+            message: &'static str,
+            json: Value,
+        }
+
+        let tests = [
+            TestError {
+                query: ".foo.bar",
+                message: r#"Cannot index number (3) with string ("bar")"#,
+                json: json!({"foo": 3}),
+            },
+            TestError {
+                query: ".foo",
+                message: r#"Cannot index string ("x") with string ("foo")"#,
+                json: json!("x"),
+            },
+            TestError {
+                query: ".[0]",
+                message: r#"Cannot index object ({}) with number (0)"#,
+                json: json!({}),
+            },
+            TestError {
+                query: ".foo",
+                message: r#"Cannot index array ([1]) with string ("foo")"#,
+                json: json!([1]),
+            },
+            TestError {
+                query: ".[0]",
+                message: "Cannot index boolean (true) with number (0)",
+                json: json!(true),
+            },
+            TestError {
+                query: ".[null]",
+                message: "Cannot index array ([]) with null (null)",
+                json: json!([]),
+            },
+            TestError {
+                query: ".foo?.bar",
+                message: r#"Cannot index number (3) with string ("bar")"#,
+                json: json!({"foo": 3}),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let error = Parser::parse(test.json, test.query).unwrap_err();
+            assert_eq!(error.exit_code(), 5, "Failed testing index {}", i);
+            assert_eq!(
+                error.to_string(),
+                test.message,
+                "Failed testing index {}",
+                i
+            );
+        }
+    }
+}
+
+mod test_assignment {
+    #[test]
+    fn test_assignment_operators() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let tests = [
+            TestParser {
+                query: r#".package.version = "0.2.0""#,
+                results: vec![json!({"package": {"name": "ruq", "version": "0.2.0"}})],
+                json: json!({"package": {"name": "ruq", "version": "0.1.0"}}),
+            },
+            TestParser {
+                query: ".a.b[1] = 1, .c = (1, 2)",
+                results: vec![
+                    json!({"a": {"b": [null, 1]}}),
+                    json!({"c": 1}),
+                    json!({"c": 2}),
+                ],
+                json: json!({}),
+            },
+            TestParser {
+                query: ".a = .b, .[] = 0, (.a, .b) = 3",
+                results: vec![
+                    json!({"a": 2, "b": 2}),
+                    json!({"a": 0, "b": 0}),
+                    json!({"a": 3, "b": 3}),
+                ],
+                json: json!({"a": 1, "b": 2}),
+            },
+            TestParser {
+                query: ".[] |= . * 2, map_values(. * 2) == (.[] |= . * 2)",
+                results: vec![json!([2, 4]), json!(true)],
+                json: json!([1, 2]),
+            },
+            TestParser {
+                query: ".[] |= select(. % 2 == 0), (.[] |= empty), .[0] |= (., 10)",
+                results: vec![json!([2, 4]), json!([]), json!([1, 2, 3, 4])],
+                json: json!([1, 2, 3, 4]),
+            },
+            TestParser {
+                query: ".a += 1, .a -= 1, .a *= 3, .a /= 2, .a %= 2, .b += 1",
+                results: vec![
+                    json!({"a": 4}),
+                    json!({"a": 2}),
+                    json!({"a": 9}),
+                    json!({"a": 1.5}),
+                    json!({"a": 1}),
+                    json!({"a": 3, "b": 1}),
+                ],
+                json: json!({"a": 3}),
+            },
+            TestParser {
+                query: ".a += (1, 2), .[] += .b",
+                results: vec![
+                    json!({"a": 2, "b": 2}),
+                    json!({"a": 3, "b": 2}),
+                    json!({"a": 3, "b": 4}),
+                ],
+                json: json!({"a": 1, "b": 2}),
+            },
+            TestParser {
+                query: ".[] //= 0, .a //= .c",
+                results: vec![
+                    json!({"a": 0, "b": 1, "c": 0}),
+                    json!({"a": null, "b": 1, "c": null}),
+                ],
+                json: json!({"a": false, "b": 1, "c": null}),
+            },
+            TestParser {
+                query: "(.. | select(. == true or . == false)) |= not, .[] |= (.a? // .)",
+                results: vec![json!([{"a": false}, [true]]), json!([true, [false]])],
+                json: json!([{"a": true}, [false]]),
+            },
+            TestParser {
+                query: "first(.[]) = 0, last(.[]) = 0, limit(2; .[]) = 0, limit(-1; .[]) = 0, .[] as $x | .[$x] = 9",
+                results: vec![
+                    json!([0, 1, 0]),
+                    json!([2, 1, 0]),
+                    json!([0, 0, 0]),
+                    json!([0, 0, 0]),
+                    json!([2, 1, 9]),
+                    json!([2, 9, 0]),
+                    json!([9, 1, 0]),
+                ],
+                json: json!([2, 1, 0]),
+            },
+            TestParser {
+                query: "def at(k): .[k]; at(\"a\") = 1 | if .b then .b else .c end |= 2",
+                results: vec![json!({"a": 1, "b": false, "c": 2})],
+                json: json!({"b": false}),
+            },
+            TestParser {
+                query: ".a = 1 | .b = .a + 1",
+                results: vec![json!({"a": 1, "b": 2})],
+                json: json!(null),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+    }
+
+    #[test]
+    fn test_invalid_assignments() {
+        use super::*;
+        use serde_json::json;
+
+        let error = Parser::parse(json!({"a": 1}), ".a.b = 1").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"Cannot index number (1) with string ("b")"#
+        );
+
+        let error = Parser::parse(json!({"a": 1}), "(.a + 1) = 2").unwrap_err();
+        assert_eq!(error.exit_code(), 5);
+        assert_eq!(
+            error.to_string(),
+            "Invalid path expression with result number (2)"
+        );
+
+        let error = Parser::parse(json!(null), ".a = .b = 1").unwrap_err();
+        assert_eq!(error.exit_code(), 3);
+
+        let error = Parser::parse(json!([]), ".[1e12] = 1").unwrap_err();
+        assert_eq!(error.to_string(), "Out of bounds array index");
+    }
+}
+
+mod test_paths {
+    #[test]
+    fn test_path_builtins() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let tests = [
+            TestParser {
+                query: "[path(..)], [path(.a[0].b, .x)], path(first(.a[]))",
+                results: vec![
+                    json!([[], ["a"], ["a", 0], ["a", 0, "b"], ["a", 1]]),
+                    json!([["a", 0, "b"], ["x"]]),
+                    json!(["a", 0]),
+                ],
+                json: json!({"a": [{"b": 1}, 2]}),
+            },
+            TestParser {
+                query: "[paths], [leaf_paths], [paths(. == 1)]",
+                results: vec![
+                    json!([["a"], ["a", 0], ["a", 0, "b"], ["a", 1]]),
+                    json!([["a", 0, "b"], ["a", 1]]),
+                    json!([["a", 0, "b"]]),
+                ],
+                json: json!({"a": [{"b": 1}, 2]}),
+            },
+            TestParser {
+                query: r#"getpath(["a", "b"]), getpath(["x", "y"]), [getpath(["a", "b"], ["a"])]"#,
+                results: vec![json!(1), json!(null), json!([1, {"b": 1}])],
+                json: json!({"a": {"b": 1}}),
+            },
+            TestParser {
+                query: r#"setpath(["a", "c"]; 2), setpath([]; 1), path(getpath(["a", "b"]))"#,
+                results: vec![json!({"a": {"b": 1, "c": 2}}), json!(1), json!(["a", "b"])],
+                json: json!({"a": {"b": 1}}),
+            },
+            TestParser {
+                query: r#"delpaths([["a", "b"], ["c", 0], ["c", 2]]), del(.c[0, 1]), del(.a, .c)"#,
+                results: vec![
+                    json!({"a": {}, "c": [2]}),
+                    json!({"a": {"b": 1}, "c": [3]}),
+                    json!({}),
+                ],
+                json: json!({"a": {"b": 1}, "c": [1, 2, 3]}),
+            },
+            TestParser {
+                query: "del(.db.password, .services[].token), pick(.db.host, .services[1].name)",
+                results: vec![
+                    json!({"db": {"host": "db"}, "services": [{"name": "api"}, {"name": "web"}]}),
+                    json!({"db": {"host": "db"}, "services": [null, {"name": "web"}]}),
+                ],
+                json: json!({
+                    "db": {"host": "db", "password": "hunter2"},
+                    "services": [{"name": "api", "token": "a"}, {"name": "web", "token": "b"}]
+                }),
+            },
+            TestParser {
+                query: "to_entries, (to_entries | from_entries), with_entries(.value += 1)",
+                results: vec![
+                    json!([{"key": "a", "value": 1}, {"key": "b", "value": 2}]),
+                    json!({"a": 1, "b": 2}),
+                    json!({"a": 2, "b": 3}),
+                ],
+                json: json!({"a": 1, "b": 2}),
+            },
+            TestParser {
+                query: "from_entries, ([10, 20] | to_entries)",
+                results: vec![
+                    json!({"a": 1, "b": 2, "1": null, "null": 3}),
+                    json!([{"key": 0, "value": 10}, {"key": 1, "value": 20}]),
+                ],
+                json: json!([
+                    {"k": "a", "v": 1},
+                    {"name": "b", "value": 2},
+                    {"key": 1},
+                    {"key": null, "Value": 3}
+                ]),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+
+        let error = Parser::parse(json!({}), r#"getpath("a")"#).unwrap_err();
+        assert_eq!(error.to_string(), "Path must be specified as an array");
+
+        let error = Parser::parse(json!([1]), "del(.[0] + 1)").unwrap_err();
+        assert_eq!(error.exit_code(), 5);
+
+        let error = Parser::parse(json!([]), "setpath([1e18]; 1)").unwrap_err();
+        assert_eq!(error.to_string(), "Out of bounds array index");
+
+        let error = Parser::parse(json!([]), "setpath([-1e18]; 1)").unwrap_err();
+        assert_eq!(error.to_string(), "Out of bounds negative array index");
+    }
+}
+
+mod test_builtin {
+    #[test]
+    fn test_filter_builtins() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let tests = [
+            TestParser {
+                query: ".[] | select(.enabled) | .name",
+                results: vec![json!("api"), json!("db")],
+                json: json!([
+                    {"name": "api", "enabled": true},
+                    {"name": "web", "enabled": false},
+                    {"name": "db", "enabled": 1},
+                ]),
+            },
+            TestParser {
+                query: ".[] | select(. > 1, . > 2)",
+                results: vec![json!(2), json!(3), json!(3)],
+                json: json!([1, 2, 3]),
+            },
+            TestParser {
+                query: "map(. * 2), map(empty), map(., .)",
+                results: vec![json!([2, 4]), json!([]), json!([1, 1, 2, 2])],
+                json: json!([1, 2]),
+            },
+            TestParser {
+                query: "map(.a)",
+                results: vec![json!([1, 2])],
+                json: json!({"x": {"a": 1}, "y": {"a": 2}}),
+            },
+            TestParser {
+                query: "map_values(. + 1), map_values(empty), map_values(., 0)",
+                results: vec![json!({"a": 2, "b": 3}), json!({}), json!({"a": 1, "b": 2})],
+                json: json!({"a": 1, "b": 2}),
+            },
+            TestParser {
+                query: "map_values(select(. > 1))",
+                results: vec![json!([2, 3])],
+                json: json!([1, 2, 3]),
+            },
+            TestParser {
+                query: "[empty], [1, empty, 2]",
+                results: vec![json!([]), json!([1, 2])],
+                json: json!(null),
+            },
+            TestParser {
+                query: "[..], [recurse]",
+                results: vec![json!([{"a": [1]}, [1], 1]), json!([{"a": [1]}, [1], 1])],
+                json: json!({"a": [1]}),
+            },
+            TestParser {
+                query: "[recurse(.children[]) | .name]",
+                results: vec![json!(["root", "a", "a1", "b"])],
+                json: json!({"name": "root", "children": [
+                    {"name": "a", "children": [{"name": "a1", "children": []}]},
+                    {"name": "b", "children": []},
+                ]}),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+    }
+
+    #[test]
+    fn test_decoded_inputs() {
+        use super::*;
+        use crate::processor::Processor;
+        use crate::{toml::Toml, yaml::Yaml};
+        use serde_json::json;
+
+        let query = "[.services[] | select(.replicas > 1) | .name]";
+        let yaml = Yaml::new(String::from(
+            "services:\n  - name: api\n    replicas: 3\n  - name: web\n    replicas: 1\n",
+        ))
+        .and_then(|e| e.to_json())
+        .unwrap();
+        let toml = Toml::new(String::from(
+            "[[services]]\nname = \"api\"\nreplicas = 3\n\n[[services]]\nname = \"web\"\nreplicas = 1\n",
+        ))
+        .and_then(|e| e.to_json())
+        .unwrap();
+
+        for json_data in [yaml, toml] {
+            assert_eq!(
+                Parser::parse(json_data, query).unwrap(),
+                vec![json!(["api"])]
+            );
+        }
+    }
+
+    #[test]
+    fn test_collection_builtins() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let tests = [
+            TestParser {
+                query: "keys, keys_unsorted, (.b | keys), [.[] | values]",
+                results: vec![
+                    json!(["a", "b"]),
+                    json!(["a", "b"]),
+                    json!([0, 1]),
+                    json!([[1, 2]]),
+                ],
+                json: json!({"b": [1, 2], "a": null}),
+            },
+            TestParser {
+                query: r#"has("a"), has("c"), (.b | has(1), has(2)), ("a", "c" | in({a: 1}))"#,
+                results: vec![
+                    json!(true),
+                    json!(false),
+                    json!(true),
+                    json!(false),
+                    json!(true),
+                    json!(false),
+                ],
+                json: json!({"b": [1, 2], "a": null}),
+            },
+            TestParser {
+                query: r#"contains({tags: ["prod"]}), contains({tags: ["dev"]}), contains({name: "ap"})"#,
+                results: vec![json!(true), json!(false), json!(true)],
+                json: json!({"name": "api", "tags": ["production", "eu"]}),
+            },
+            TestParser {
+                query: r#"contains("bar"), ("bar" | inside("foobar")), ([1] | inside([1, 2]))"#,
+                results: vec![json!(true), json!(true), json!(true)],
+                json: json!("foobar"),
+            },
+            TestParser {
+                query: "add, (map(tostring) | add), ([] | add), ([[1], [2]] | add)",
+                results: vec![json!(6), json!("123"), json!(null), json!([1, 2])],
+                json: json!([1, 2, 3]),
+            },
+            TestParser {
+                query: "any, all, any(. > 2), all(. > 1), any(.[]; . == 2), all(empty; false)",
+                results: vec![
+                    json!(true),
+                    json!(true),
+                    json!(true),
+                    json!(false),
+                    json!(true),
+                    json!(true),
+                ],
+                json: json!([1, 2, 3]),
+            },
+            TestParser {
+                query: "flatten, flatten(1), min, max, reverse, ([] | min)",
+                results: vec![
+                    json!([1, 2, 3, 4]),
+                    json!([1, 2, [3, [4]]]),
+                    json!(1),
+                    json!([2, [3, [4]]]),
+                    json!([[2, [3, [4]]], 1]),
+                    json!(null),
+                ],
+                json: json!([1, [2, [3, [4]]]]),
+            },
+            TestParser {
+                query: r#"indices(", "), index(", "), rindex(", "), indices("é"), ("abc" | reverse)"#,
+                results: vec![
+                    json!([1, 4, 9]),
+                    json!(1),
+                    json!(9),
+                    json!([7, 8]),
+                    json!("cba"),
+                ],
+                json: json!("a, b, céé, d"),
+            },
+            TestParser {
+                query: "indices(1), indices([1, 2]), index(1), rindex(1), (null | index(1))",
+                results: vec![
+                    json!([1, 3, 5]),
+                    json!([1, 5]),
+                    json!(1),
+                    json!(5),
+                    json!(null),
+                ],
+                json: json!([0, 1, 2, 1, 3, 1, 2]),
+            },
+            TestParser {
+                query: "[.[] | tostring], [.[] | type], (.[1] | tonumber)",
+                results: vec![
+                    json!(["1", "1", "[1]", r#"{"a":null}"#, "null"]),
+                    json!(["number", "string", "array", "object", "null"]),
+                    json!(1),
+                ],
+                json: json!([1, "1", [1], {"a": null}, null]),
+            },
+            TestParser {
+                query: r#""length", ["length"] | length"#,
+                results: vec![json!(6), json!(1)],
+                json: json!(null),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+
+        let error = Parser::parse(json!("a"), "contains(1)").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"string ("a") and number (1) cannot have their containment checked"#
+        );
+
+        let error = Parser::parse(json!([1]), "flatten(-1)").unwrap_err();
+        assert_eq!(error.to_string(), "Flatten depth must not be negative");
+
+        let error = Parser::parse(json!("1a"), "tonumber").unwrap_err();
+        assert_eq!(error.to_string(), r#"Cannot parse "1a" as a number"#);
+    }
+
+    #[test]
+    fn test_string_builtins() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let tests = [
+            TestParser {
+                query: "length, utf8bytelength, .[1:3], index(\"ö\"), explode[1]",
+                results: vec![json!(5), json!(7), json!("öß"), json!(1), json!(246)],
+                json: json!("Göße!"),
+            },
+            TestParser {
+                query: r#"split(", "), [splits(",")], split(""), ("" | split(","))"#,
+                results: vec![
+                    json!(["a", "b", "c"]),
+                    json!(["a", " b", " c"]),
+                    json!(["a", ",", " ", "b", ",", " ", "c"]),
+                    json!([]),
+                ],
+                json: json!("a, b, c"),
+            },
+            TestParser {
+                query: r#"join("-"), join(""), ([] | join(","))"#,
+                results: vec![json!("a-1--true"), json!("a1true"), json!("")],
+                json: json!(["a", 1, null, true]),
+            },
+            TestParser {
+                query: "ascii_downcase, ascii_upcase, trim, ltrim, rtrim",
+                results: vec![
+                    json!(" héllo wörld "),
+                    json!(" HéLLO WöRLD "),
+                    json!("Héllo Wörld"),
+                    json!("Héllo Wörld "),
+                    json!(" Héllo Wörld"),
+                ],
+                json: json!(" Héllo Wörld "),
+            },
+            TestParser {
+                query: r#"ltrimstr("v"), rtrimstr(".0"), ltrimstr("x"), startswith("v1"), endswith("1"), (1 | ltrimstr("1"))"#,
+                results: vec![
+                    json!("1.2.0"),
+                    json!("v1.2"),
+                    json!("v1.2.0"),
+                    json!(true),
+                    json!(false),
+                    json!(1),
+                ],
+                json: json!("v1.2.0"),
+            },
+            TestParser {
+                query: "explode, (explode | implode), ([65, 97] | map(ascii) | add)",
+                results: vec![json!([97, 233, 128512]), json!("aé😀"), json!("Aa")],
+                json: json!("aé😀"),
+            },
+            TestParser {
+                query: "tojson, (tojson | fromjson), @text, (.a | @text), ([1, @text] | tojson)",
+                results: vec![
+                    json!(r#"{"a":[1,"x"]}"#),
+                    json!({"a": [1, "x"]}),
+                    json!(r#"{"a":[1,"x"]}"#),
+                    json!("[1,\"x\"]"),
+                    json!(r#"[1,"{\"a\":[1,\"x\"]}"]"#),
+                ],
+                json: json!({"a": [1, "x"]}),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+
+        let error = Parser::parse(json!("a"), "startswith(1)").unwrap_err();
+        assert_eq!(error.to_string(), "startswith() requires string inputs");
+
+        let error = Parser::parse(json!([[1]]), r#"join(",")"#).unwrap_err();
+        assert_eq!(error.to_string(), "Cannot join array ([1])");
+
+        let error = Parser::parse(json!(null), "@nope").unwrap_err();
+        assert_eq!(error.exit_code(), 3);
+        assert_eq!(
+            error.to_string(),
+            "invalid filter: @nope is not a valid format at byte 0"
+        );
+    }
+
+    #[test]
+    fn test_regex_builtins() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let tests = [
+            TestParser {
+                query: r#"test("V\\d"), test("V\\d"; "i"), test(["V\\d", "ix"]), test("1 . 2"; "x")"#,
+                results: vec![json!(false), json!(true), json!(true), json!(true)],
+                json: json!("version v1.2"),
+            },
+            TestParser {
+                query: r#"match("(?<major>\\d+)\\.(\\d+)"), [match("\\d+"; "g") | .offset]"#,
+                results: vec![
+                    json!({
+                        "offset": 2,
+                        "length": 3,
+                        "string": "1.2",
+                        "captures": [
+                            {"offset": 2, "length": 1, "string": "1", "name": "major"},
+                            {"offset": 4, "length": 1, "string": "2", "name": null}
+                        ]
+                    }),
+                    json!([2, 4, 10, 12]),
+                ],
+                json: json!("é 1.2 and 3.45"),
+            },
+            TestParser {
+                query: r#"capture("(?<host>[^:]+):(?<port>\\d+)(?<path>/.*)?"), [capture("(?<n>\\d)"; "g")]"#,
+                results: vec![
+                    json!({"host": "db.local", "port": "5432", "path": null}),
+                    json!([{"n": "5"}, {"n": "4"}, {"n": "3"}, {"n": "2"}]),
+                ],
+                json: json!("db.local:5432"),
+            },
+            TestParser {
+                query: r#"[scan("\\d+")], [scan("(\\d)(\\d)?")], [scan("B"; "i")]"#,
+                results: vec![
+                    json!(["12", "3"]),
+                    json!([["1", "2"], ["3", null]]),
+                    json!(["b"]),
+                ],
+                json: json!("a12b3"),
+            },
+            TestParser {
+                query: r#"split(", *"; null), [splits("\\d")], [splits("X"; "i")]"#,
+                results: vec![
+                    json!(["x1", "y2", "z"]),
+                    json!(["x", ", y", ",z"]),
+                    json!(["", "1, y2,z"]),
+                ],
+                json: json!("x1, y2,z"),
+            },
+            TestParser {
+                query: r#"sub("(?<d>\\d)"; "<" + .d + ">"), gsub("(?<d>\\d)"; "<" + .d + ">"), gsub("B"; "c"; "i"), gsub(""; "-"), gsub(""; "-"; "n")"#,
+                results: vec![
+                    json!("a<1> b2"),
+                    json!("a<1> b<2>"),
+                    json!("a1 c2"),
+                    json!("-a-1- -b-2-"),
+                    json!("a1 b2"),
+                ],
+                json: json!("a1 b2"),
+            },
+            TestParser {
+                query: r#"[sub("b"; "1", "2")], [gsub("b"; "1", "2")], gsub("(?<x>b)(?<y>c)?"; .y // "-")"#,
+                results: vec![
+                    json!(["a1cb", "a2cb"]),
+                    json!(["a1c1", "a1c2", "a2c1", "a2c2"]),
+                    json!("ac-"),
+                ],
+                json: json!("abcb"),
+            },
+            TestParser {
+                query: r#"[.[] | select(test("^(dev|test)-"))], (.[0] | sub("-[^-]+$"; ""))"#,
+                results: vec![json!(["dev-api", "test-db"]), json!("dev")],
+                json: json!(["dev-api", "prod-api", "test-db"]),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+
+        let error = Parser::parse(json!("a"), r#"test("(")"#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#""(" is not a valid regex: unclosed group"#
+        );
+
+        let error = Parser::parse(json!("a"), r#"test("a"; "gq")"#).unwrap_err();
+        assert_eq!(error.to_string(), "gq is not a valid modifier string");
+
+        let error = Parser::parse(json!(1), r#"test("a")"#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "number (1) cannot be matched, as it is not a string"
+        );
+    }
+
+    #[test]
+    fn test_sorting_builtins() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let services = json!([
+            {"name": "web", "tier": 2, "port": 80},
+            {"name": "db", "tier": 1, "port": 5432},
+            {"name": "cache", "tier": 1, "port": 6379},
+            {"name": "api", "tier": 2, "port": 8080}
+        ]);
+
+        let tests = [
+            TestParser {
+                query: "sort",
+                results: vec![json!([null, false, true, 1, 3, "a", "b", [0], [1], {}, {"a": 1}])],
+                json: json!([3, "b", {"a": 1}, null, [1], true, "a", {}, false, 1, [0]]),
+            },
+            TestParser {
+                query: "[sort_by(.tier)[].name], [sort_by(.tier, .name)[].name], [sort_by(-.port)[].port]",
+                results: vec![
+                    json!(["db", "cache", "web", "api"]),
+                    json!(["cache", "db", "api", "web"]),
+                    json!([8080, 6379, 5432, 80]),
+                ],
+                json: services.clone(),
+            },
+            TestParser {
+                query: "group_by(.tier) | map(map(.name))",
+                results: vec![json!([["db", "cache"], ["web", "api"]])],
+                json: services.clone(),
+            },
+            TestParser {
+                query: "[unique_by(.tier)[].name], min_by(.tier).name, max_by(.tier).name, max_by(.port).name",
+                results: vec![json!(["db", "web"]), json!("db"), json!("api"), json!("api")],
+                json: services,
+            },
+            TestParser {
+                query: "unique, unique_by(length), min_by(length), max_by(length), ([] | min_by(.))",
+                results: vec![
+                    json!(["ab", "c", "d", "efg"]),
+                    json!(["c", "ab", "efg"]),
+                    json!("c"),
+                    json!("efg"),
+                    json!(null),
+                ],
+                json: json!(["ab", "c", "d", "ab", "efg"]),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+
+        let error = Parser::parse(json!({"a": 1}), "sort").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"object ({"a":1}) cannot be sorted, as it is not an array"#
+        );
+    }
+
+    #[test]
+    fn test_math_builtins() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let tests = [
+            TestParser {
+                query: "map(floor), map(ceil), map(round), map(trunc), map(fabs)",
+                results: vec![
+                    json!([1, -2, 2]),
+                    json!([2, -1, 3]),
+                    json!([2, -2, 3]),
+                    json!([1, -1, 2]),
+                    json!([1.5, 1.5, 2.5]),
+                ],
+                json: json!([1.5, -1.5, 2.5]),
+            },
+            TestParser {
+                query: "sqrt, log2, exp10, (1 | exp | log), (0 | sin, cos), significand, logb",
+                results: vec![
+                    json!(4),
+                    json!(4),
+                    json!(10000000000000000_i64),
+                    json!(1),
+                    json!(0),
+                    json!(1),
+                    json!(1),
+                    json!(4),
+                ],
+                json: json!(16),
+            },
+            TestParser {
+                query: "(.[0] | sqrt), .[0] * 2, length, add, .[0] / 8, .[1] - 0.5",
+                results: vec![
+                    json!(2),
+                    json!(8),
+                    json!(2),
+                    json!(7),
+                    json!(0.5),
+                    json!(2.5),
+                ],
+                json: json!([4, 3]),
+            },
+            TestParser {
+                query: "pow(.[]; 2), fmin(.[0]; .[1]), fmax(.[0]; .[1]), fmod(.[1]; .[0])",
+                results: vec![json!(9), json!(16), json!(3), json!(4), json!(1)],
+                json: json!([3, 4]),
+            },
+            TestParser {
+                query: ".[] | [isinfinite, isnan, isnormal]",
+                results: vec![
+                    json!([false, false, true]),
+                    json!([false, false, false]),
+                    json!([false, false, true]),
+                ],
+                json: json!([1, 0, 1.7976931348623157e308]),
+            },
+            TestParser {
+                query: "infinite, -infinite, nan, (-1 | sqrt), ([.[] | .cpu * 1.5 | ceil] | add)",
+                results: vec![
+                    json!(f64::MAX),
+                    json!(f64::MIN),
+                    json!(null),
+                    json!(null),
+                    json!(5),
+                ],
+                json: json!([{"cpu": 1}, {"cpu": 1.5}]),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+
+        let error = Parser::parse(json!("1"), "floor").unwrap_err();
+        assert_eq!(error.to_string(), r#"string ("1") number required"#);
+
+        let error = Parser::parse(json!(null), r#"pow(2; "a")"#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"number (2) and string ("a") number required"#
+        );
+
+        let error = Parser::parse(json!({}), ".missing | isnan").unwrap_err();
+        assert_eq!(error.to_string(), "null (null) number required");
+    }
+
+    #[test]
+    fn test_date_builtins() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let tests = [
+            TestParser {
+                query: "todate, gmtime, (gmtime | mktime), (gmtime | todate), strftime(\"%a %d %b %Y %j %I:%M%p\")",
+                results: vec![
+                    json!("2015-03-05T23:51:47Z"),
+                    json!([2015, 2, 5, 23, 51, 47, 4, 63]),
+                    json!(1425599507),
+                    json!("2015-03-05T23:51:47Z"),
+                    json!("Thu 05 Mar 2015 064 11:51PM"),
+                ],
+                json: json!(1425599507),
+            },
+            TestParser {
+                query: r#"fromdate, strptime("%Y-%m-%dT%H:%M:%SZ"), ("5 march 2015 11:51 pm -0100" | strptime("%d %B %Y %I:%M %p %z") | todate)"#,
+                results: vec![
+                    json!(1425599507),
+                    json!([2015, 2, 5, 23, 51, 47, 4, 63]),
+                    json!("2015-03-06T00:51:00Z"),
+                ],
+                json: json!("2015-03-05T23:51:47Z"),
+            },
+            TestParser {
+                query: r#"dateadd("seconds"; 86400), datesub("seconds"; 3600), (dateadd("seconds"; 3600, 7200) | todate)"#,
+                results: vec![
+                    json!(1706745600),
+                    json!(1706655600),
+                    json!("2024-01-31T01:00:00Z"),
+                    json!("2024-01-31T02:00:00Z"),
+                ],
+                json: json!(1706659200),
+            },
+            TestParser {
+                query: r#".a < .b, (.a | fromdate), (.b | todate), "\(.a)", ([.a, .b] | sort | map(fromdate))"#,
+                results: vec![
+                    json!(false),
+                    json!(296667120),
+                    json!("1979-05-27T10:00:00Z"),
+                    json!("1979-05-27T07:32:00-08:00"),
+                    json!([296647200, 296667120]),
+                ],
+                json: json!({
+                    "a": {"$__toml_private_datetime": "1979-05-27T07:32:00-08:00"},
+                    "b": {"$__toml_private_datetime": "1979-05-27T10:00:00Z"},
+                }),
+            },
+            TestParser {
+                query: r#"map(select(fromdate > ("2024-01-01" | fromdate))), (now | type)"#,
+                results: vec![json!(["2024-03-01T08:00:00+01:00"]), json!("number")],
+                json: json!(["2023-12-31T23:00:00Z", "2024-03-01T08:00:00+01:00"]),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+
+        let error = Parser::parse(json!("March 2015"), "fromdate").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"date "March 2015" is not an RFC 3339 date like "2015-03-05" or "2015-03-05T23:51:47Z""#
+        );
+
+        let error = Parser::parse(json!("2015-13-45"), "fromdate").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"date "2015-13-45" is not an RFC 3339 date like "2015-03-05" or "2015-03-05T23:51:47Z""#
+        );
+
+        let error = Parser::parse(json!(0), r#"strftime("%Q")"#).unwrap_err();
+        assert_eq!(error.to_string(), "%Q is not a supported conversion");
+
+        let error = Parser::parse(json!([2015]), "mktime").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "array ([2015]) is not a broken down time, expected an array of 6 numbers"
+        );
+
+        let error = Parser::parse(json!(1e17), "todate").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "100000000000000000 is out of range for a timestamp"
+        );
+
+        let error = Parser::parse(json!([1e18, 0, 1, 0, 0, 0]), "mktime").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "1000000000000000000 is out of range for a broken down time"
+        );
+    }
+
+    #[test]
+    fn test_string_interpolation() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let tests = [
+            TestParser {
+                query: r#""\(1 + 1)", "\(.port / 2)", @json "\([.port * 2])", ([.port + 1, 0.5] | @csv, @sh)"#,
+                results: vec![
+                    json!("2"),
+                    json!("40"),
+                    json!("[160]"),
+                    json!("81,0.5"),
+                    json!("81 0.5"),
+                ],
+                json: json!({"port": 80}),
+            },
+            TestParser {
+                query: r#""\(.name):\(.port)", "\(1, 2)-\("a", "b")", "\("nested \(.name)")\t\u00e9\ud83d\ude00""#,
+                results: vec![
+                    json!("web:80"),
+                    json!("1-a"),
+                    json!("2-a"),
+                    json!("1-b"),
+                    json!("2-b"),
+                    json!("nested web\té😀"),
+                ],
+                json: json!({"name": "web", "port": 80}),
+            },
+            TestParser {
+                query: r#"@sh "echo \(.name)", @sh "\(.args)", (.args | @sh)"#,
+                results: vec![
+                    json!("echo 'it'\\''s'"),
+                    json!("'-v' 'a b' 1"),
+                    json!("'-v' 'a b' 1"),
+                ],
+                json: json!({"name": "it's", "args": ["-v", "a b", 1]}),
+            },
+            TestParser {
+                query: "@csv, @tsv, @json, @text",
+                results: vec![
+                    json!(r#"1,"a,""b""",,true"#),
+                    json!("1\ta,\"b\"\t\ttrue"),
+                    json!(r#"[1,"a,\"b\"",null,true]"#),
+                    json!(r#"[1,"a,\"b\"",null,true]"#),
+                ],
+                json: json!([1, "a,\"b\"", null, true]),
+            },
+            TestParser {
+                query: r#"@uri, @html, @base64, (@base64 | @base64d), @uri "https://x.io/?q=\(.)", @base64 "plain""#,
+                results: vec![
+                    json!("%3Ca%20href%3D%27x%27%3E%C3%A9~"),
+                    json!("&lt;a href=&#39;x&#39;&gt;é~"),
+                    json!("PGEgaHJlZj0neCc+w6l+"),
+                    json!("<a href='x'>é~"),
+                    json!("https://x.io/?q=%3Ca%20href%3D%27x%27%3E%C3%A9~"),
+                    json!("plain"),
+                ],
+                json: json!("<a href='x'>é~"),
+            },
+            TestParser {
+                query: r#"def f: "\(.)!"; [.[] as $x | "\($x): \($x | f)"], {"key-\(.[0])": 1}"#,
+                results: vec![json!(["a: a!", "b: b!"]), json!({"key-a": 1})],
+                json: json!(["a", "b"]),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+
+        let error = Parser::parse(json!({"a": 1}), "@csv").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"object ({"a":1}) cannot be csv-formatted, only an array can be"#
+        );
+
+        let error = Parser::parse(json!([{}]), "@sh").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "object ({}) can not be escaped for shell"
+        );
+
+        let error = Parser::parse(json!("a"), "@base64d").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"string ("a") is not valid base64 data"#
+        );
+
+        assert!(Parser::parse(json!(null), r#""\(1 2)""#).is_err());
+        assert!(Parser::parse(json!(null), r#""\(1""#).is_err());
+        assert!(Parser::parse(json!(null), r#""\q""#).is_err());
+        assert!(Parser::parse(json!(null), r#""\($x)""#).is_err());
+    }
+}
+
+mod test_stream {
+    #[test]
+    fn test_multiple_outputs() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let tests = [
+            TestParser {
+                query: ".items[].name",
+                results: vec![json!("ruq"), json!("jq")],
+                json: json!({"items": [{"name": "ruq"}, {"name": "jq"}]}),
+            },
+            TestParser {
+                query: ".[]",
+                results: vec![json!(1), json!([2])],
+                json: json!({"a": 1, "b": [2]}),
+            },
+            TestParser {
+                query: ".[] | .[] | . * 2",
+                results: vec![json!(2), json!(4), json!(6)],
+                json: json!([[1, 2], [], [3]]),
+            },
+            TestParser {
+                query: ".[]",
+                results: vec![],
+                json: json!([]),
+            },
+            TestParser {
+                query: ".a[] + .b[]",
+                results: vec![json!(11), json!(12), json!(21), json!(22)],
+                json: json!({"a": [1, 2], "b": [10, 20]}),
+            },
+            TestParser {
+                query: "{name: .[].n, v: 1}",
+                results: vec![json!({"name": "a", "v": 1}), json!({"name": "b", "v": 1})],
+                json: json!([{"n": "a"}, {"n": "b"}]),
+            },
+            TestParser {
+                query: "[.[] | .id]",
+                results: vec![json!([1, 2])],
+                json: json!([{"id": 1}, {"id": 2}]),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+    }
+
+    #[test]
+    fn test_construction() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let tests = [
+            TestParser {
+                query: ".a, .b",
+                results: vec![json!(1), json!(2)],
+                json: json!({"a": 1, "b": 2}),
+            },
+            TestParser {
+                query: "[.items[].id]",
+                results: vec![json!([1, 2])],
+                json: json!({"items": [{"id": 1}, {"id": 2}]}),
+            },
+            TestParser {
+                query: "[.a, .b | . + 1]",
+                results: vec![json!([2, 3])],
+                json: json!({"a": 1, "b": 2}),
+            },
+            TestParser {
+                query: "[]",
+                results: vec![json!([])],
+                json: json!(null),
+            },
+            TestParser {
+                query: "{name, version: .package.version, (.k): .v}",
+                results: vec![json!({"name": "ruq", "version": "0.1.0", "edition": 2021})],
+                json: json!({
+                    "name": "ruq",
+                    "package": {"version": "0.1.0"},
+                    "k": "edition",
+                    "v": 2021,
+                }),
+            },
+            TestParser {
+                query: r#"{"a b", (.k, "c"): .v | length}"#,
+                results: vec![json!({"a b": 1, "x": 2}), json!({"a b": 1, "c": 2})],
+                json: json!({"a b": 1, "k": "x", "v": [1, 2]}),
+            },
+            TestParser {
+                query: "{a: (1, 2)}",
+                results: vec![json!({"a": 1}), json!({"a": 2})],
+                json: json!(null),
+            },
+            TestParser {
+                query: "{a: -1, b: -.v, c: - -.v | tostring}",
+                results: vec![json!({"a": -1, "b": -2, "c": "2"})],
+                json: json!({"v": 2}),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+
+        let error = Parser::parse(json!({"k": 1}), "{(.k): 1}").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Object keys must be strings, not number (1)"
+        );
+    }
+
+    #[test]
+    fn test_outputs_before_error() {
+        use super::*;
+        use serde_json::json;
+
+        let parser = Parser::new(".[] | 1 / .").unwrap();
+        let mut outputs = parser.run(json!([1, 0, -1]));
+
+        assert_eq!(outputs.next().unwrap().unwrap(), json!(1));
+        assert!(outputs.next().unwrap().is_err());
+    }
+}
+
+mod test_predicate {
+    #[test]
+    fn test_comparison() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let tests = [
+            TestParser {
+                query: ".a == 1, .a != 1, .a < 2, .a <= 1, .a > 1, .a >= 2",
+                results: vec![
+                    json!(true),
+                    json!(false),
+                    json!(true),
+                    json!(true),
+                    json!(false),
+                    json!(false),
+                ],
+                json: json!({"a": 1.0}),
+            },
+            TestParser {
+                query: r#"null < false, false < true, true < 0, 0 < "", "" < [], [] < {}"#,
+                results: vec![json!(true); 6],
+                json: json!(null),
+            },
+            TestParser {
+                query: ".a == {b: [1, 2]}",
+                results: vec![json!(true)],
+                json: json!({"a": {"b": [1, 2]}}),
+            },
+            TestParser {
+                query: "1 + 1 == 2",
+                results: vec![json!(true)],
+                json: json!(null),
+            },
+            TestParser {
+                query: ".[] > 1",
+                results: vec![json!(false), json!(true)],
+                json: json!([1, 2]),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+    }
+
+    #[test]
+    fn test_boolean() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestParser {
+            query: &'static str,
+            results: Vec<Value>,
+            json: Value,
+        }
+
+        let tests = [
+            TestParser {
+                query: ".a and .b, .a or .b, .b or .c, .c and .a",
+                results: vec![json!(false), json!(true), json!(true), json!(true)],
+                json: json!({"a": 0, "b": null, "c": "x"}),
+            },
+            TestParser {
+                query: "(true, false) and (true, false)",
+                results: vec![json!(true), json!(false), json!(false)],
+                json: json!(null),
+            },
+            TestParser {
+                query: "false and (1 + {}), true or (1 + {})",
+                results: vec![json!(false), json!(true)],
+                json: json!(null),
+            },
+            TestParser {
+                query: ".[] | not",
+                results: vec![json!(true), json!(true), json!(false), json!(false)],
+                json: json!([null, false, 0, []]),
+            },
+            TestParser {
+                query: "true or false and false, (true or false) and false",
+                results: vec![json!(true), json!(false)],
+                json: json!(null),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let parsed = Parser::parse(test.json, test.query).unwrap();
+            assert_eq!(parsed, test.results, "Failed testing index {}", i);
+        }
+    }
+}
+
+mod test {
+    #[test]
+    fn make_valid_json() {
+        use super::Parser;
+        use serde_json::Value;
+        use std::str::FromStr;
+
+        struct TestParser {
+            query: String,
+            results: Vec<Value>,
+            json: Value,
+        }
+        let tests = [
+            TestParser {
+                query: String::from(r#"{"a": .a}"#),
+                results: vec![Value::from_str(r#"{"a":"Hello"}"#).unwrap()],
+                json: serde_json::json!({
+                    "a": "Hello",
+                    "b": 1,
+                    "c": true,
+                }),
+            },
+            TestParser {
+                query: String::from(r#"{"a": .a[0]}"#),
+                results: vec![Value::from_str(r#"{"a":{"a":55,"c":100}}"#).unwrap()],
+                json: Value::from_str(
+                    r#"{"a": [{"a": 55, "c": 100}, {"b": 2}], "b": 1,"c": true}"#,
+                )
+                .unwrap(),
+            },
+            TestParser {
+                query: String::from(" .a[0]"),
+                results: vec![Value::from_str(r#"{"a":55,"c":100}"#).unwrap()],
+                json: Value::from_str(r#"{"a": [{"a": 55, "c": 100}, {"b": 2}]}"#).unwrap(),
+            },
+            TestParser {
+                query: String::from(r#"{"a": "a.b"}"#),
+                results: vec![Value::from_str(r#"{"a": "a.b"}"#).unwrap()],
+                json: Value::from_str(r#"{}"#).unwrap(),
+            },
+            TestParser {
+                query: String::from(r#"{"a": ".a", "b": .a}"#),
+                results: vec![Value::from_str(r#"{"a":".a", "b": "Hello"}"#).unwrap()],
+                json: serde_json::json!({
+                    "a": "Hello",
+                    "b": 1,
+                    "c": true,
+                }),
+            },
+            TestParser {
+                query: String::from(r#"{"say \"hi\"": .a, "b|c": "x | y"}"#),
+                results: vec![
+                    Value::from_str(r#"{"say \"hi\"": "Hello", "b|c": "x | y"}"#).unwrap(),
+                ],
+                json: serde_json::json!({
+                    "a": "Hello",
+                }),
+            },
+            TestParser {
+                query: String::from(r#"[.a, (.b | length), "length"] | .[1]"#),
+                results: vec![serde_json::json!(2)],
+                json: serde_json::json!({
+                    "a": "Hello",
+                    "b": [1, 2],
+                }),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let value = Parser::parse(test.json.clone(), &test.query).unwrap();
+            assert_eq!(value, test.results, "Failed testing index {}", i);
+        }
+    }
+
+    #[test]
+    fn report_errors() {
+        use super::Parser;
+        use crate::Error;
+        use serde_json::json;
+
+        let tests = [
+            (".a[", json!({}), 3),
+            (r#"{"a": 1"#, json!({}), 3),
+            ("foo", json!({}), 3),
+            (r#""unterminated"#, json!({}), 3),
+            (r#".a + "b""#, json!({"a": 1}), 5),
+            (".a / 0", json!({"a": 1}), 5),
+            (".[]", json!(true), 5),
+            ("length", json!(true), 5),
+        ];
+
+        for (i, (query, json, exit_code)) in tests.into_iter().enumerate() {
+            let error: Error = Parser::parse(json, query).unwrap_err();
+            assert_eq!(error.exit_code(), exit_code, "Failed testing index {}", i);
         }
     }
 }
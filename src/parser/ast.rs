@@ -0,0 +1,1183 @@
+use super::builtin;
+use super::lexer::{Lexer, Segment, Token};
+use super::operator::{negate, Operator};
+use crate::error::{Error, Span};
+use serde_json::Value;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Ast {
+    Identity,
+    Field(Box<Ast>, String),
+    Index(Box<Ast>, Box<Ast>),
+    Iterate(Box<Ast>),
+    Literal(Value),
+    Collect(Box<Ast>),
+    /// Object construction; each key is a filter that must yield strings.
+    Object(Vec<(Ast, Ast)>),
+    Pipe(Box<Ast>, Box<Ast>),
+    Comma(Box<Ast>, Box<Ast>),
+    And(Box<Ast>, Box<Ast>),
+    Or(Box<Ast>, Box<Ast>),
+    /// `a // b`, the truthy outputs of `a` or else the outputs of `b`.
+    Alternative(Box<Ast>, Box<Ast>),
+    /// `if cond then a else b end`, `elif` chains nest in the else branch.
+    If(Box<Ast>, Box<Ast>, Box<Ast>),
+    Binary(Operator, Box<Ast>, Box<Ast>),
+    Negate(Box<Ast>),
+    /// Call of a builtin or defined function with its filter arguments.
+    Call(String, Vec<Ast>),
+    /// `def name(params): body; rest`
+    Def(Box<Definition>, Box<Ast>),
+    Variable(String),
+    /// `@name`, formats the input as a string.
+    Format(String),
+    /// `"a \(b)"` or `@name "a \(b)"`, the outputs of every interpolation
+    /// are formatted and spliced into the string.
+    Interpolate(String, Vec<Fragment>),
+    /// `source as pattern ?// pattern | body`
+    Bind(Box<Ast>, Vec<Pattern>, Box<Ast>),
+    /// `try body catch handler`, also written `body?` without a handler.
+    Try(Box<Ast>, Option<Box<Ast>>),
+    /// `reduce source as pattern (init; update)`
+    Reduce(Box<Ast>, Pattern, Box<Ast>, Box<Ast>),
+    /// `foreach source as pattern (init; update; extract)`, every state is
+    /// emitted as is when there's no extract.
+    Foreach(Box<Ast>, Pattern, Box<Ast>, Box<Ast>, Option<Box<Ast>>),
+    /// `path = value` and the update forms `|=`, `+=`, `//=` and so on.
+    Assign(Assignment, Box<Ast>, Box<Ast>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fragment {
+    Literal(String),
+    Interpolation(Ast),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Assignment {
+    /// `=`, every path gets the value of the right hand side.
+    Set,
+    /// `|=`, every value is replaced by the right hand side run on it.
+    Update,
+    /// `+=`, `-=`, `*=`, `/=` and `%=`.
+    Arithmetic(Operator),
+    /// `//=`, falsy values get the value of the right hand side.
+    Alternative,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Definition {
+    pub name: String,
+    pub params: Vec<Param>,
+    pub body: Ast,
+}
+
+/// Parameter of a defined function. Filter parameters are closures evaluated
+/// in the caller's scope, `$name` value parameters are bound once for every
+/// output of their argument and can be called as filters as well.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Param {
+    Filter(String),
+    Value(String),
+}
+
+impl Param {
+    pub fn name(&self) -> &str {
+        match self {
+            Param::Filter(name) | Param::Value(name) => name,
+        }
+    }
+}
+
+/// Destructuring pattern of an `as` binding.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern {
+    Variable(String),
+    Array(Vec<Pattern>),
+    /// Each entry's key is a filter, the value under it is destructured by
+    /// every pattern of the entry. `{$a: [$b]}` binds both `$a` and `$b`.
+    Object(Vec<(Ast, Vec<Pattern>)>),
+}
+
+impl Pattern {
+    /// Names of the variables the pattern binds.
+    pub fn variables(&self) -> Vec<&str> {
+        match self {
+            Pattern::Variable(name) => vec![name],
+            Pattern::Array(patterns) => patterns.iter().flat_map(Pattern::variables).collect(),
+            Pattern::Object(entries) => entries
+                .iter()
+                .flat_map(|(_, patterns)| patterns.iter().flat_map(Pattern::variables))
+                .collect(),
+        }
+    }
+}
+
+impl Ast {
+    pub fn parse(filter: &str) -> Result<Ast, Error> {
+        let tokens = Lexer::new(filter).tokenize()?;
+        let mut parser = AstParser {
+            tokens,
+            position: 0,
+            end: filter.len(),
+            variables: vec![],
+            functions: vec![],
+        };
+
+        let ast = parser.pipe()?;
+        if parser.peek().is_some() {
+            return Err(parser
+                .unexpected()
+                .with_hint("expected an operator, '|' or end of filter"));
+        }
+
+        Ok(ast)
+    }
+}
+
+/// Precedence of the right associative `//` operator.
+const ALTERNATIVE: u8 = 1;
+/// Precedence of the non-associative assignment operators.
+const ASSIGNMENT: u8 = 2;
+/// Precedence of the non-associative comparison operators.
+const COMPARISON: u8 = 5;
+
+struct AstParser {
+    tokens: Vec<(Token, Span)>,
+    position: usize,
+    end: usize,
+    /// Variables in scope at the current position.
+    variables: Vec<String>,
+    /// Names and arities of the functions defined at the current position.
+    functions: Vec<(String, usize)>,
+}
+
+impl AstParser {
+    fn peek(&self) -> Option<&Token> {
+        self.peek_nth(0)
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<&Token> {
+        self.tokens.get(self.position + n).map(|(token, _)| token)
+    }
+
+    fn span(&self) -> Span {
+        match self.tokens.get(self.position) {
+            Some((_, span)) => span.clone(),
+            None => self.end..self.end,
+        }
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.position += 1;
+            return true;
+        }
+
+        false
+    }
+
+    /// Error pointing at the current token, or at the end of the filter.
+    fn unexpected(&self) -> Error {
+        match self.peek() {
+            Some(token) => Error::syntax(format!("unexpected {}", token), self.span()),
+            None => Error::syntax("unexpected end of filter", self.span()),
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Ident(ident)) if ident == keyword) {
+            self.position += 1;
+            return true;
+        }
+
+        false
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), Error> {
+        if self.eat_keyword(keyword) {
+            return Ok(());
+        }
+
+        Err(self
+            .unexpected()
+            .with_hint(format!("expected '{}'", keyword)))
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), Error> {
+        if self.eat(&token) {
+            return Ok(());
+        }
+
+        Err(self.unexpected().with_hint(format!("expected {}", token)))
+    }
+
+    fn pipe(&mut self) -> Result<Ast, Error> {
+        if self.eat_keyword("def") {
+            return self.definition();
+        }
+
+        let lhs = self.comma()?;
+        if self.eat(&Token::Pipe) {
+            return Ok(Ast::Pipe(Box::new(lhs), Box::new(self.pipe()?)));
+        }
+
+        Ok(lhs)
+    }
+
+    fn comma(&mut self) -> Result<Ast, Error> {
+        let mut lhs = self.binary(0)?;
+        while self.eat(&Token::Comma) {
+            lhs = Ast::Comma(Box::new(lhs), Box::new(self.binary(0)?));
+        }
+
+        Ok(lhs)
+    }
+
+    /// Parses operators below `,` by precedence climbing. Every operator is
+    /// left associative except `//`, which is right associative, and
+    /// assignments and comparisons, which don't associate at all: `1 < 2 < 3`
+    /// is a syntax error.
+    fn binary(&mut self, min_precedence: u8) -> Result<Ast, Error> {
+        let mut lhs = self.unary()?;
+        let (mut assigned, mut compared) = (false, false);
+
+        while let Some(precedence) = self.peek().and_then(Self::precedence) {
+            if precedence < min_precedence {
+                break;
+            }
+
+            if precedence == ASSIGNMENT {
+                if assigned {
+                    return Err(self
+                        .unexpected()
+                        .with_hint("assignments don't chain, use '|'"));
+                }
+
+                assigned = true;
+            }
+
+            if precedence == COMPARISON {
+                if compared {
+                    return Err(self
+                        .unexpected()
+                        .with_hint("comparisons don't chain, use 'and'"));
+                }
+
+                compared = true;
+            }
+
+            let token = self.next();
+            let rhs = match precedence {
+                ALTERNATIVE => self.binary(precedence)?,
+                _ => self.binary(precedence + 1)?,
+            };
+            lhs = match token {
+                Some(Token::SlashSlash) => Ast::Alternative(Box::new(lhs), Box::new(rhs)),
+                Some(Token::Ident(ident)) if ident == "or" => Ast::Or(Box::new(lhs), Box::new(rhs)),
+                Some(Token::Ident(ident)) if ident == "and" => {
+                    Ast::And(Box::new(lhs), Box::new(rhs))
+                }
+                Some(token) if precedence == ASSIGNMENT => {
+                    let assignment = match token {
+                        Token::Assign => Assignment::Set,
+                        Token::PipeAssign => Assignment::Update,
+                        Token::SlashSlashAssign => Assignment::Alternative,
+                        token => Assignment::Arithmetic(Self::operator(&token).unwrap()),
+                    };
+                    Ast::Assign(assignment, Box::new(lhs), Box::new(rhs))
+                }
+                token => {
+                    let operator = token.as_ref().and_then(Self::operator).unwrap();
+                    Ast::Binary(operator, Box::new(lhs), Box::new(rhs))
+                }
+            };
+        }
+
+        Ok(lhs)
+    }
+
+    /// Binding power of an infix operator, higher binds tighter.
+    fn precedence(token: &Token) -> Option<u8> {
+        let precedence = match token {
+            Token::SlashSlash => ALTERNATIVE,
+            Token::Assign
+            | Token::PipeAssign
+            | Token::PlusAssign
+            | Token::MinusAssign
+            | Token::StarAssign
+            | Token::SlashAssign
+            | Token::SlashSlashAssign
+            | Token::PercentAssign => ASSIGNMENT,
+            Token::Ident(ident) if ident == "or" => 3,
+            Token::Ident(ident) if ident == "and" => 4,
+            Token::Equal
+            | Token::NotEqual
+            | Token::Less
+            | Token::LessEqual
+            | Token::Greater
+            | Token::GreaterEqual => COMPARISON,
+            Token::Plus | Token::Minus => 6,
+            Token::Star | Token::Slash | Token::Percent => 7,
+            _ => return None,
+        };
+
+        Some(precedence)
+    }
+
+    fn operator(token: &Token) -> Option<Operator> {
+        let operator = match token {
+            Token::Plus | Token::PlusAssign => Operator::Addition,
+            Token::Minus | Token::MinusAssign => Operator::Subtration,
+            Token::Star | Token::StarAssign => Operator::Multiplication,
+            Token::Slash | Token::SlashAssign => Operator::Division,
+            Token::Percent | Token::PercentAssign => Operator::Modulo,
+            Token::Equal => Operator::Equal,
+            Token::NotEqual => Operator::NotEqual,
+            Token::Less => Operator::Less,
+            Token::LessEqual => Operator::LessEqual,
+            Token::Greater => Operator::Greater,
+            Token::GreaterEqual => Operator::GreaterEqual,
+            _ => return None,
+        };
+
+        Some(operator)
+    }
+
+    /// Unary minus binds tighter than any infix operator. Negated number
+    /// literals are folded into the literal.
+    fn unary(&mut self) -> Result<Ast, Error> {
+        if !self.eat(&Token::Minus) {
+            let term = self.postfix_term()?;
+            if self.eat_keyword("as") {
+                return self.binding(term);
+            }
+
+            return Ok(term);
+        }
+
+        let ast = self.unary()?;
+        Self::negated(ast)
+    }
+
+    /// Negative number literals are folded, anything else is negated when
+    /// evaluated.
+    fn negated(ast: Ast) -> Result<Ast, Error> {
+        match ast {
+            Ast::Literal(Value::Number(number)) => Ok(Ast::Literal(negate(Value::Number(number))?)),
+            ast => Ok(Ast::Negate(Box::new(ast))),
+        }
+    }
+
+    /// Parses the rest of `term as $name | body`. The variables are in scope
+    /// until the end of the body, which extends as far right as possible.
+    fn binding(&mut self, source: Ast) -> Result<Ast, Error> {
+        let scope = self.variables.len();
+
+        let mut patterns = vec![self.pattern()?];
+        while self.eat(&Token::QuestionAlternative) {
+            patterns.push(self.pattern()?);
+        }
+
+        self.expect(Token::Pipe)?;
+        let body = self.pipe();
+        self.variables.truncate(scope);
+
+        Ok(Ast::Bind(Box::new(source), patterns, Box::new(body?)))
+    }
+
+    fn pattern(&mut self) -> Result<Pattern, Error> {
+        match self.next() {
+            Some(Token::Variable(name)) => {
+                self.variables.push(name.clone());
+                Ok(Pattern::Variable(name))
+            }
+            Some(Token::LeftBracket) => {
+                let mut patterns = vec![self.pattern()?];
+                while self.eat(&Token::Comma) {
+                    patterns.push(self.pattern()?);
+                }
+
+                self.expect(Token::RightBracket)?;
+                Ok(Pattern::Array(patterns))
+            }
+            Some(Token::LeftBrace) => {
+                let mut entries = vec![self.object_pattern_entry()?];
+                while self.eat(&Token::Comma) {
+                    entries.push(self.object_pattern_entry()?);
+                }
+
+                self.expect(Token::RightBrace)?;
+                Ok(Pattern::Object(entries))
+            }
+            _ => {
+                self.position -= 1;
+                Err(self.unexpected().with_hint("expected '$name', '[' or '{'"))
+            }
+        }
+    }
+
+    /// Parses `$name`, `$name: pattern`, `key: pattern` or `(key): pattern`.
+    fn object_pattern_entry(&mut self) -> Result<(Ast, Vec<Pattern>), Error> {
+        let key = match self.next() {
+            Some(Token::Variable(name)) => {
+                self.variables.push(name.clone());
+                let key = Ast::Literal(Value::String(name.clone()));
+                let mut patterns = vec![Pattern::Variable(name)];
+                if self.eat(&Token::Colon) {
+                    patterns.push(self.pattern()?);
+                }
+
+                return Ok((key, patterns));
+            }
+            Some(Token::Ident(key) | Token::Str(key)) => Ast::Literal(Value::String(key)),
+            Some(Token::LeftParen) => {
+                let key = self.pipe()?;
+                self.expect(Token::RightParen)?;
+                key
+            }
+            _ => {
+                self.position -= 1;
+                return Err(self.unexpected().with_hint("expected an object key"));
+            }
+        };
+
+        self.expect(Token::Colon)?;
+        Ok((key, vec![self.pattern()?]))
+    }
+
+    fn postfix_term(&mut self) -> Result<Ast, Error> {
+        let mut term = self.term()?;
+
+        loop {
+            term = match self.peek() {
+                Some(Token::Field(_)) => match self.next() {
+                    Some(Token::Field(key)) => Ast::Field(Box::new(term), key),
+                    _ => unreachable!(),
+                },
+                Some(Token::LeftBracket) => self.index(term)?,
+                Some(Token::Question) => {
+                    self.position += 1;
+                    Ast::Try(Box::new(term), None)
+                }
+                Some(Token::Dot) if self.peek_nth(1) == Some(&Token::LeftBracket) => {
+                    self.position += 1;
+                    self.index(term)?
+                }
+                Some(Token::Dot) if matches!(self.peek_nth(1), Some(Token::Str(_))) => {
+                    self.position += 1;
+                    self.quoted_field(term)
+                }
+                _ => return Ok(term),
+            }
+        }
+    }
+
+    /// Parses `[]`, `[index]` and the slices `[from:to]`, `[from:]` and
+    /// `[:to]`.
+    fn index(&mut self, term: Ast) -> Result<Ast, Error> {
+        self.expect(Token::LeftBracket)?;
+        if self.eat(&Token::RightBracket) {
+            return Ok(Ast::Iterate(Box::new(term)));
+        }
+
+        if self.eat(&Token::Colon) {
+            let to = self.pipe()?;
+            self.expect(Token::RightBracket)?;
+            return Ok(Self::slice(term, Ast::Literal(Value::Null), to));
+        }
+
+        let index = self.pipe()?;
+        if self.eat(&Token::Colon) {
+            let to = match self.peek() {
+                Some(Token::RightBracket) => Ast::Literal(Value::Null),
+                _ => self.pipe()?,
+            };
+            self.expect(Token::RightBracket)?;
+            return Ok(Self::slice(term, index, to));
+        }
+
+        self.expect(Token::RightBracket)?;
+        Ok(Ast::Index(Box::new(term), Box::new(index)))
+    }
+
+    /// A slice indexes with a `{"start", "end"}` object, which is also the
+    /// slice's key in paths.
+    fn slice(term: Ast, from: Ast, to: Ast) -> Ast {
+        let bounds = Ast::Object(vec![
+            (Ast::Literal(Value::from("start")), from),
+            (Ast::Literal(Value::from("end")), to),
+        ]);
+        Ast::Index(Box::new(term), Box::new(bounds))
+    }
+
+    /// Parses the quoted key of `."key"`.
+    fn quoted_field(&mut self, term: Ast) -> Ast {
+        match self.next() {
+            Some(Token::Str(key)) => Ast::Field(Box::new(term), key),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Parses the tokens of every interpolation in a string on their own,
+    /// with the variables and functions in scope at the string.
+    fn interpolate(
+        &mut self,
+        format: String,
+        segments: Vec<Segment>,
+        span: Span,
+    ) -> Result<Ast, Error> {
+        let mut fragments = vec![];
+        for segment in segments {
+            let tokens = match segment {
+                Segment::Literal(string) => {
+                    fragments.push(Fragment::Literal(string));
+                    continue;
+                }
+                Segment::Interpolation(tokens) => tokens,
+            };
+
+            let end = tokens.last().map_or(span.end, |(_, span)| span.end);
+            let mut parser = AstParser {
+                tokens,
+                position: 0,
+                end,
+                variables: self.variables.clone(),
+                functions: self.functions.clone(),
+            };
+
+            let ast = parser.pipe()?;
+            if parser.peek().is_some() {
+                return Err(parser.unexpected().with_hint("expected ')'"));
+            }
+            fragments.push(Fragment::Interpolation(ast));
+        }
+
+        Ok(Ast::Interpolate(format, fragments))
+    }
+
+    fn term(&mut self) -> Result<Ast, Error> {
+        let span = self.span();
+        let term = match self.next() {
+            Some(Token::Dot) => match self.peek() {
+                Some(Token::LeftBracket) => return self.index(Ast::Identity),
+                Some(Token::Str(_)) => self.quoted_field(Ast::Identity),
+                _ => Ast::Identity,
+            },
+            Some(Token::Field(key)) => Ast::Field(Box::new(Ast::Identity), key),
+            Some(Token::Number(number)) => Ast::Literal(Value::Number(number)),
+            Some(Token::Str(string)) => Ast::Literal(Value::String(string)),
+            Some(Token::Template(segments)) => {
+                self.interpolate(String::from("text"), segments, span)?
+            }
+            Some(Token::Variable(name)) => {
+                if !self.variables.contains(&name) {
+                    return Err(Error::syntax(format!("${} is not defined", name), span));
+                }
+
+                Ast::Variable(name)
+            }
+            Some(Token::Format(name)) => {
+                if builtin::find_format(&name).is_none() {
+                    return Err(Error::syntax(
+                        format!("@{} is not a valid format", name),
+                        span,
+                    ));
+                }
+
+                // Only the interpolations of a string following a format are
+                // formatted, a string without any stays as is.
+                match self.peek().cloned() {
+                    Some(Token::Str(string)) => {
+                        self.position += 1;
+                        Ast::Literal(Value::String(string))
+                    }
+                    Some(Token::Template(segments)) => {
+                        let span = self.span();
+                        self.position += 1;
+                        self.interpolate(name, segments, span)?
+                    }
+                    _ => Ast::Format(name),
+                }
+            }
+            Some(Token::DotDot) => Ast::Call(String::from("recurse"), vec![]),
+            Some(Token::Ident(ident)) => match ident.as_str() {
+                "null" => Ast::Literal(Value::Null),
+                "true" => Ast::Literal(Value::Bool(true)),
+                "false" => Ast::Literal(Value::Bool(false)),
+                "if" => self.conditional()?,
+                "try" => {
+                    let body = self.postfix_term()?;
+                    let handler = match self.eat_keyword("catch") {
+                        true => Some(Box::new(self.postfix_term()?)),
+                        false => None,
+                    };
+                    Ast::Try(Box::new(body), handler)
+                }
+                "reduce" | "foreach" => self.fold(&ident)?,
+                _ => self.call(ident, span)?,
+            },
+            Some(Token::LeftParen) => {
+                let ast = self.pipe()?;
+                self.expect(Token::RightParen)?;
+                ast
+            }
+            Some(Token::LeftBracket) => {
+                if self.eat(&Token::RightBracket) {
+                    return Ok(Ast::Literal(Value::Array(vec![])));
+                }
+
+                let ast = self.pipe()?;
+                self.expect(Token::RightBracket)?;
+                Ast::Collect(Box::new(ast))
+            }
+            Some(Token::LeftBrace) => {
+                let mut entries = vec![];
+                if self.eat(&Token::RightBrace) {
+                    return Ok(Ast::Object(entries));
+                }
+
+                loop {
+                    entries.push(self.object_entry()?);
+                    if !self.eat(&Token::Comma) {
+                        break;
+                    }
+                }
+
+                self.expect(Token::RightBrace)?;
+                Ast::Object(entries)
+            }
+            _ => {
+                self.position -= 1;
+                return Err(self.unexpected().with_hint("expected a value"));
+            }
+        };
+
+        Ok(term)
+    }
+
+    /// Parses the rest of `if c then a (elif c then a)* (else b)? end`. A
+    /// missing else branch passes the input through.
+    fn conditional(&mut self) -> Result<Ast, Error> {
+        let condition = self.pipe()?;
+        self.expect_keyword("then")?;
+        let then = self.pipe()?;
+
+        let otherwise = if self.eat_keyword("elif") {
+            return Ok(Ast::If(
+                Box::new(condition),
+                Box::new(then),
+                Box::new(self.conditional()?),
+            ));
+        } else if self.eat_keyword("else") {
+            self.pipe()?
+        } else {
+            Ast::Identity
+        };
+
+        self.expect_keyword("end")?;
+        Ok(Ast::If(
+            Box::new(condition),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    /// Parses the rest of `reduce source as pattern (init; update)` or
+    /// `foreach source as pattern (init; update; extract)`. The pattern's
+    /// variables are in scope in update and extract only.
+    fn fold(&mut self, keyword: &str) -> Result<Ast, Error> {
+        let source = self.postfix_term()?;
+        self.expect_keyword("as")?;
+
+        let scope = self.variables.len();
+        let pattern = self.pattern()?;
+        let variables = self.variables.split_off(scope);
+
+        self.expect(Token::LeftParen)?;
+        let init = self.pipe()?;
+        self.expect(Token::Semicolon)?;
+
+        self.variables.extend(variables);
+        let update = self.pipe()?;
+        let extract = match keyword {
+            "foreach" if self.eat(&Token::Semicolon) => Some(Box::new(self.pipe()?)),
+            _ => None,
+        };
+        self.variables.truncate(scope);
+        self.expect(Token::RightParen)?;
+
+        let (source, init, update) = (Box::new(source), Box::new(init), Box::new(update));
+        match keyword {
+            "reduce" => Ok(Ast::Reduce(source, pattern, init, update)),
+            _ => Ok(Ast::Foreach(source, pattern, init, update, extract)),
+        }
+    }
+
+    /// Parses the rest of `def name(params): body; rest`. The function is in
+    /// scope in its own body and in the rest of the pipe, its parameters only
+    /// in the body.
+    fn definition(&mut self) -> Result<Ast, Error> {
+        let name = match self.next() {
+            Some(Token::Ident(name)) => name,
+            _ => {
+                self.position -= 1;
+                return Err(self.unexpected().with_hint("expected a function name"));
+            }
+        };
+
+        let mut params = vec![];
+        if self.eat(&Token::LeftParen) {
+            loop {
+                params.push(match self.next() {
+                    Some(Token::Ident(name)) => Param::Filter(name),
+                    Some(Token::Variable(name)) => Param::Value(name),
+                    _ => {
+                        self.position -= 1;
+                        return Err(self.unexpected().with_hint("expected a parameter name"));
+                    }
+                });
+
+                if !self.eat(&Token::Semicolon) {
+                    break;
+                }
+            }
+
+            self.expect(Token::RightParen)?;
+        }
+        self.expect(Token::Colon)?;
+
+        let (variables, functions) = (self.variables.len(), self.functions.len());
+        self.functions.push((name.clone(), params.len()));
+        for param in &params {
+            if let Param::Value(name) = param {
+                self.variables.push(name.clone());
+            }
+            self.functions.push((param.name().to_string(), 0));
+        }
+
+        let body = self.pipe();
+        self.variables.truncate(variables);
+        self.functions.truncate(functions + 1);
+        let body = body?;
+
+        self.expect(Token::Semicolon)?;
+
+        let rest = self.pipe();
+        self.functions.truncate(functions);
+
+        let definition = Definition { name, params, body };
+        Ok(Ast::Def(Box::new(definition), Box::new(rest?)))
+    }
+
+    /// Parses the arguments of a call to `name`, `name(a; b)`.
+    fn call(&mut self, name: String, span: Span) -> Result<Ast, Error> {
+        let mut args = vec![];
+        if self.eat(&Token::LeftParen) {
+            loop {
+                args.push(self.pipe()?);
+                if !self.eat(&Token::Semicolon) {
+                    break;
+                }
+            }
+
+            self.expect(Token::RightParen)?;
+        }
+
+        let defined = self
+            .functions
+            .iter()
+            .any(|(n, arity)| *n == name && *arity == args.len());
+        if !defined && builtin::find(&name, args.len()).is_none() {
+            let span = span.start..self.tokens[self.position - 1].1.end;
+            return Err(Error::syntax(
+                format!("{}/{} is not defined", name, args.len()),
+                span,
+            ));
+        }
+
+        Ok(Ast::Call(name, args))
+    }
+
+    /// Parses `key: value`, `(key): value` or the `key` and `$key` shorthands
+    /// for `key: .key` and `key: $key`.
+    fn object_entry(&mut self) -> Result<(Ast, Ast), Error> {
+        let key = match self.peek() {
+            Some(Token::Str(key) | Token::Ident(key)) => {
+                let key = key.clone();
+                self.position += 1;
+
+                if !self.eat(&Token::Colon) {
+                    let value = Ast::Field(Box::new(Ast::Identity), key.clone());
+                    return Ok((Ast::Literal(Value::String(key)), value));
+                }
+
+                return Ok((Ast::Literal(Value::String(key)), self.object_value()?));
+            }
+            Some(Token::Variable(name)) => {
+                let name = name.clone();
+                let value = self.term()?;
+                return Ok((Ast::Literal(Value::String(name)), value));
+            }
+            Some(Token::Template(_)) => self.term()?,
+            Some(Token::LeftParen) => {
+                self.position += 1;
+                let key = self.pipe()?;
+                self.expect(Token::RightParen)?;
+                key
+            }
+            _ => return Err(self.unexpected().with_hint("expected an object key")),
+        };
+
+        self.expect(Token::Colon)?;
+        Ok((key, self.object_value()?))
+    }
+
+    /// Object values bind tighter than `,`, so only pipes of terms, which
+    /// may be negated, are allowed without parentheses.
+    fn object_value(&mut self) -> Result<Ast, Error> {
+        let value = self.object_term()?;
+        if self.eat(&Token::Pipe) {
+            return Ok(Ast::Pipe(Box::new(value), Box::new(self.object_value()?)));
+        }
+
+        Ok(value)
+    }
+
+    fn object_term(&mut self) -> Result<Ast, Error> {
+        if self.eat(&Token::Minus) {
+            let term = self.object_term()?;
+            return Self::negated(term);
+        }
+
+        self.postfix_term()
+    }
+}
+
+mod test_ast {
+    #[test]
+    fn parse_filters() {
+        use super::*;
+
+        struct TestAst {
+            filter: &'static str,
+            ast: Ast,
+        }
+
+        let tests = [
+            TestAst {
+                filter: "length",
+                ast: Ast::Call("length".into(), vec![]),
+            },
+            TestAst {
+                filter: " length",
+                ast: Ast::Call("length".into(), vec![]),
+            },
+            TestAst {
+                filter: ".a[0]",
+                ast: Ast::Index(
+                    Box::new(Ast::Field(Box::new(Ast::Identity), "a".into())),
+                    Box::new(Ast::Literal(0.into())),
+                ),
+            },
+            TestAst {
+                filter: ".[] | .a",
+                ast: Ast::Pipe(
+                    Box::new(Ast::Iterate(Box::new(Ast::Identity))),
+                    Box::new(Ast::Field(Box::new(Ast::Identity), "a".into())),
+                ),
+            },
+            TestAst {
+                filter: ".a == 1 or .b and not",
+                ast: Ast::Or(
+                    Box::new(Ast::Binary(
+                        Operator::Equal,
+                        Box::new(Ast::Field(Box::new(Ast::Identity), "a".into())),
+                        Box::new(Ast::Literal(1.into())),
+                    )),
+                    Box::new(Ast::And(
+                        Box::new(Ast::Field(Box::new(Ast::Identity), "b".into())),
+                        Box::new(Ast::Call("not".into(), vec![])),
+                    )),
+                ),
+            },
+            TestAst {
+                filter: "1 + 2 * -3 > -.a - (4 - 5)",
+                ast: Ast::Binary(
+                    Operator::Greater,
+                    Box::new(Ast::Binary(
+                        Operator::Addition,
+                        Box::new(Ast::Literal(1.into())),
+                        Box::new(Ast::Binary(
+                            Operator::Multiplication,
+                            Box::new(Ast::Literal(2.into())),
+                            Box::new(Ast::Literal((-3).into())),
+                        )),
+                    )),
+                    Box::new(Ast::Binary(
+                        Operator::Subtration,
+                        Box::new(Ast::Negate(Box::new(Ast::Field(
+                            Box::new(Ast::Identity),
+                            "a".into(),
+                        )))),
+                        Box::new(Ast::Binary(
+                            Operator::Subtration,
+                            Box::new(Ast::Literal(4.into())),
+                            Box::new(Ast::Literal(5.into())),
+                        )),
+                    )),
+                ),
+            },
+            TestAst {
+                filter: "if .a then 1 elif .b then 2 end // 3 // 4",
+                ast: Ast::Alternative(
+                    Box::new(Ast::If(
+                        Box::new(Ast::Field(Box::new(Ast::Identity), "a".into())),
+                        Box::new(Ast::Literal(1.into())),
+                        Box::new(Ast::If(
+                            Box::new(Ast::Field(Box::new(Ast::Identity), "b".into())),
+                            Box::new(Ast::Literal(2.into())),
+                            Box::new(Ast::Identity),
+                        )),
+                    )),
+                    Box::new(Ast::Alternative(
+                        Box::new(Ast::Literal(3.into())),
+                        Box::new(Ast::Literal(4.into())),
+                    )),
+                ),
+            },
+            TestAst {
+                filter: ". as [$a, {b: $c}] ?// $a | $a",
+                ast: Ast::Bind(
+                    Box::new(Ast::Identity),
+                    vec![
+                        Pattern::Array(vec![
+                            Pattern::Variable("a".into()),
+                            Pattern::Object(vec![(
+                                Ast::Literal("b".into()),
+                                vec![Pattern::Variable("c".into())],
+                            )]),
+                        ]),
+                        Pattern::Variable("a".into()),
+                    ],
+                    Box::new(Ast::Variable("a".into())),
+                ),
+            },
+            TestAst {
+                filter: "def inc($by; f): f + $by; inc(1; .)",
+                ast: Ast::Def(
+                    Box::new(Definition {
+                        name: "inc".into(),
+                        params: vec![Param::Value("by".into()), Param::Filter("f".into())],
+                        body: Ast::Binary(
+                            Operator::Addition,
+                            Box::new(Ast::Call("f".into(), vec![])),
+                            Box::new(Ast::Variable("by".into())),
+                        ),
+                    }),
+                    Box::new(Ast::Call(
+                        "inc".into(),
+                        vec![Ast::Literal(1.into()), Ast::Identity],
+                    )),
+                ),
+            },
+            TestAst {
+                filter: ".a[]?, try (1 / .) catch . + 1",
+                ast: Ast::Comma(
+                    Box::new(Ast::Try(
+                        Box::new(Ast::Iterate(Box::new(Ast::Field(
+                            Box::new(Ast::Identity),
+                            "a".into(),
+                        )))),
+                        None,
+                    )),
+                    Box::new(Ast::Binary(
+                        Operator::Addition,
+                        Box::new(Ast::Try(
+                            Box::new(Ast::Binary(
+                                Operator::Division,
+                                Box::new(Ast::Literal(1.into())),
+                                Box::new(Ast::Identity),
+                            )),
+                            Some(Box::new(Ast::Identity)),
+                        )),
+                        Box::new(Ast::Literal(1.into())),
+                    )),
+                ),
+            },
+            TestAst {
+                filter: r#"{"a": .b, c: "length"}"#,
+                ast: Ast::Object(vec![
+                    (
+                        Ast::Literal("a".into()),
+                        Ast::Field(Box::new(Ast::Identity), "b".into()),
+                    ),
+                    (Ast::Literal("c".into()), Ast::Literal("length".into())),
+                ]),
+            },
+            TestAst {
+                filter: "{name, (.k): .v}",
+                ast: Ast::Object(vec![
+                    (
+                        Ast::Literal("name".into()),
+                        Ast::Field(Box::new(Ast::Identity), "name".into()),
+                    ),
+                    (
+                        Ast::Field(Box::new(Ast::Identity), "k".into()),
+                        Ast::Field(Box::new(Ast::Identity), "v".into()),
+                    ),
+                ]),
+            },
+            TestAst {
+                filter: "[.a, .b | .c]",
+                ast: Ast::Collect(Box::new(Ast::Pipe(
+                    Box::new(Ast::Comma(
+                        Box::new(Ast::Field(Box::new(Ast::Identity), "a".into())),
+                        Box::new(Ast::Field(Box::new(Ast::Identity), "b".into())),
+                    )),
+                    Box::new(Ast::Field(Box::new(Ast::Identity), "c".into())),
+                ))),
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let ast = Ast::parse(test.filter).unwrap();
+            assert_eq!(ast, test.ast, "Failed testing index {}", i);
+        }
+    }
+
+    #[test]
+    fn report_syntax_errors() {
+        use super::*;
+
+        struct TestAst {
+            filter: &'static str,
+            span: Span,
+            hint: Option<&'static str>,
+        }
+
+        let tests = [
+            TestAst {
+                filter: ".foo[1 + ",
+                span: 9..9,
+                hint: Some("expected a value"),
+            },
+            TestAst {
+                filter: "1 < 2 < 3",
+                span: 6..7,
+                hint: Some("comparisons don't chain, use 'and'"),
+            },
+            TestAst {
+                filter: ".a and 1 == 2 != 3",
+                span: 14..16,
+                hint: Some("comparisons don't chain, use 'and'"),
+            },
+            TestAst {
+                filter: "1 * -",
+                span: 5..5,
+                hint: Some("expected a value"),
+            },
+            TestAst {
+                filter: "if . then 1 else 2",
+                span: 18..18,
+                hint: Some("expected 'end'"),
+            },
+            TestAst {
+                filter: "if . | 1 end",
+                span: 9..12,
+                hint: Some("expected 'then'"),
+            },
+            TestAst {
+                filter: ".foo[1",
+                span: 6..6,
+                hint: Some("expected ']'"),
+            },
+            TestAst {
+                filter: ".a | (.b | .c",
+                span: 13..13,
+                hint: Some("expected ')'"),
+            },
+            TestAst {
+                filter: "{a: 1,, b: 2}",
+                span: 6..7,
+                hint: Some("expected an object key"),
+            },
+            TestAst {
+                filter: ".a 1",
+                span: 3..4,
+                hint: Some("expected an operator, '|' or end of filter"),
+            },
+            TestAst {
+                filter: "map(.a; .b) | .",
+                span: 0..11,
+                hint: None,
+            },
+            TestAst {
+                filter: "(. as $x | $x), $x",
+                span: 16..18,
+                hint: None,
+            },
+            TestAst {
+                filter: ". as [$a, 1] | $a",
+                span: 10..11,
+                hint: Some("expected '$name', '[' or '{'"),
+            },
+            TestAst {
+                filter: ". as $a $a",
+                span: 8..10,
+                hint: Some("expected '|'"),
+            },
+            TestAst {
+                filter: "def f(g): g; f",
+                span: 13..14,
+                hint: None,
+            },
+            TestAst {
+                filter: "def f(g): g; g",
+                span: 13..14,
+                hint: None,
+            },
+            TestAst {
+                filter: "def f($a): 1; $a",
+                span: 14..16,
+                hint: None,
+            },
+            TestAst {
+                filter: "def f: 1 f",
+                span: 9..10,
+                hint: Some("expected ';'"),
+            },
+            TestAst {
+                filter: "reduce .[] as $x ($x; 1)",
+                span: 18..20,
+                hint: None,
+            },
+            TestAst {
+                filter: "reduce .[] as $x (0; . + $x; 1)",
+                span: 27..28,
+                hint: Some("expected ')'"),
+            },
+            TestAst {
+                filter: ".a | lenght",
+                span: 5..11,
+                hint: None,
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            match Ast::parse(test.filter) {
+                Err(Error::Syntax { span, hint, .. }) => {
+                    assert_eq!(span, test.span, "Failed testing index {}", i);
+                    assert_eq!(hint.as_deref(), test.hint, "Failed testing index {}", i);
+                }
+                result => panic!("Failed testing index {}: {:?}", i, result),
+            }
+        }
+    }
+}
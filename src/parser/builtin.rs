@@ -0,0 +1,2112 @@
+use super::ast::Ast;
+use super::date::{self, Time};
+use super::evaluator::{flat_map, once, truthy, Env, Evaluator, Outputs};
+use super::format;
+use super::operator::{compare, number, type_id, type_name, Operator};
+use super::path::{self, Path, Paths};
+use crate::Error;
+use regex::{Regex, RegexBuilder};
+use serde_json::{Map, Value};
+use std::{
+    cmp::Ordering,
+    iter,
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A builtin receives its filter arguments unevaluated, so it decides how
+/// often and against which input each of them runs.
+pub type Function = for<'a> fn(&'a [Ast], &Env<'a>, Value) -> Outputs<'a>;
+
+/// Builtins by name and number of arguments.
+const BUILTINS: &[(&str, usize, Function)] = &[
+    ("empty", 0, empty),
+    ("error", 0, error),
+    ("error", 1, error_with),
+    ("length", 0, length),
+    ("not", 0, not),
+    ("select", 1, select),
+    ("map", 1, map),
+    ("map_values", 1, map_values),
+    ("recurse", 0, recurse),
+    ("recurse", 1, recurse_with),
+    ("limit", 2, limit),
+    ("first", 0, first),
+    ("first", 1, first_of),
+    ("last", 0, last),
+    ("last", 1, last_of),
+    ("until", 2, until),
+    ("while", 2, while_),
+    ("range", 1, range),
+    ("range", 2, range),
+    ("range", 3, range),
+    ("path", 1, path),
+    ("paths", 0, paths),
+    ("paths", 1, paths_of),
+    ("leaf_paths", 0, leaf_paths),
+    ("getpath", 1, getpath),
+    ("setpath", 2, setpath),
+    ("delpaths", 1, delpaths),
+    ("del", 1, del),
+    ("pick", 1, pick),
+    ("to_entries", 0, to_entries),
+    ("from_entries", 0, from_entries),
+    ("with_entries", 1, with_entries),
+    ("keys", 0, keys),
+    ("keys_unsorted", 0, keys),
+    ("values", 0, values),
+    ("has", 1, has),
+    ("in", 1, in_),
+    ("contains", 1, contains),
+    ("inside", 1, inside),
+    ("add", 0, add),
+    ("any", 0, any),
+    ("any", 1, any),
+    ("any", 2, any),
+    ("all", 0, all),
+    ("all", 1, all),
+    ("all", 2, all),
+    ("flatten", 0, flatten),
+    ("flatten", 1, flatten),
+    ("min", 0, min),
+    ("max", 0, max),
+    ("min_by", 1, min),
+    ("max_by", 1, max),
+    ("sort", 0, sort),
+    ("sort_by", 1, sort),
+    ("group_by", 1, group_by),
+    ("unique", 0, unique),
+    ("unique_by", 1, unique),
+    ("reverse", 0, reverse),
+    ("indices", 1, indices),
+    ("index", 1, index),
+    ("rindex", 1, rindex),
+    ("tostring", 0, tostring),
+    ("tonumber", 0, tonumber),
+    ("type", 0, type_),
+    ("utf8bytelength", 0, utf8bytelength),
+    ("split", 1, split),
+    ("split", 2, split_matches),
+    ("splits", 1, splits),
+    ("splits", 2, splits),
+    ("join", 1, join),
+    ("ascii_downcase", 0, ascii_downcase),
+    ("ascii_upcase", 0, ascii_upcase),
+    ("ltrimstr", 1, ltrimstr),
+    ("rtrimstr", 1, rtrimstr),
+    ("startswith", 1, startswith),
+    ("endswith", 1, endswith),
+    ("trim", 0, trim),
+    ("ltrim", 0, ltrim),
+    ("rtrim", 0, rtrim),
+    ("explode", 0, explode),
+    ("implode", 0, implode),
+    ("ascii", 0, ascii),
+    ("tojson", 0, tojson),
+    ("fromjson", 0, fromjson),
+    ("test", 1, test),
+    ("test", 2, test),
+    ("match", 1, match_),
+    ("match", 2, match_),
+    ("capture", 1, capture),
+    ("capture", 2, capture),
+    ("scan", 1, scan),
+    ("scan", 2, scan),
+    ("sub", 2, sub),
+    ("sub", 3, sub),
+    ("gsub", 2, gsub),
+    ("gsub", 3, gsub),
+    ("floor", 0, floor),
+    ("ceil", 0, ceil),
+    ("round", 0, round),
+    ("trunc", 0, trunc),
+    ("fabs", 0, fabs),
+    ("sqrt", 0, sqrt),
+    ("cbrt", 0, cbrt),
+    ("exp", 0, exp),
+    ("exp2", 0, exp2),
+    ("exp10", 0, exp10),
+    ("expm1", 0, expm1),
+    ("log", 0, log),
+    ("log2", 0, log2),
+    ("log10", 0, log10),
+    ("log1p", 0, log1p),
+    ("logb", 0, logb),
+    ("significand", 0, significand),
+    ("sin", 0, sin),
+    ("cos", 0, cos),
+    ("tan", 0, tan),
+    ("asin", 0, asin),
+    ("acos", 0, acos),
+    ("atan", 0, atan),
+    ("sinh", 0, sinh),
+    ("cosh", 0, cosh),
+    ("tanh", 0, tanh),
+    ("asinh", 0, asinh),
+    ("acosh", 0, acosh),
+    ("atanh", 0, atanh),
+    ("pow", 2, pow),
+    ("atan2", 2, atan2),
+    ("fmin", 2, fmin),
+    ("fmax", 2, fmax),
+    ("fmod", 2, fmod),
+    ("infinite", 0, infinite),
+    ("nan", 0, nan),
+    ("isinfinite", 0, isinfinite),
+    ("isnan", 0, isnan),
+    ("isnormal", 0, isnormal),
+    ("now", 0, now),
+    ("gmtime", 0, gmtime),
+    ("mktime", 0, mktime),
+    ("strftime", 1, strftime),
+    ("strptime", 1, strptime),
+    ("todate", 0, todate),
+    ("todateiso8601", 0, todate),
+    ("fromdate", 0, fromdate),
+    ("fromdateiso8601", 0, fromdate),
+    ("dateadd", 2, dateadd),
+    ("datesub", 2, datesub),
+];
+
+/// Formats a value as a string, used by `@name`.
+pub type Format = fn(Value) -> Result<String, Error>;
+
+/// Formats by name, without the `@`.
+const FORMATS: &[(&str, Format)] = &[
+    ("text", text),
+    ("json", format::json),
+    ("csv", format::csv),
+    ("tsv", format::tsv),
+    ("sh", format::sh),
+    ("uri", format::uri),
+    ("html", format::html),
+    ("base64", format::base64),
+    ("base64d", format::base64d),
+];
+
+/// The path expression form of a builtin, used on the left of assignments.
+/// `json_data` lives at `path` within the document being updated.
+pub type PathFunction = for<'a> fn(&'a [Ast], &Env<'a>, Path, Value) -> Paths<'a>;
+
+/// Builtins that can be used as path expressions.
+const PATH_BUILTINS: &[(&str, usize, PathFunction)] = &[
+    ("empty", 0, empty_paths),
+    ("error", 0, error_paths),
+    ("error", 1, error_paths),
+    ("select", 1, select_paths),
+    ("recurse", 0, recurse_paths),
+    ("recurse", 1, recurse_paths),
+    ("limit", 2, limit_paths),
+    ("first", 0, first_paths),
+    ("first", 1, first_paths),
+    ("last", 0, last_paths),
+    ("last", 1, last_paths),
+    ("getpath", 1, getpath_paths),
+];
+
+pub fn find(name: &str, arity: usize) -> Option<Function> {
+    BUILTINS
+        .iter()
+        .find(|(n, a, _)| *n == name && *a == arity)
+        .map(|(_, _, function)| *function)
+}
+
+pub fn find_path(name: &str, arity: usize) -> Option<PathFunction> {
+    PATH_BUILTINS
+        .iter()
+        .find(|(n, a, _)| *n == name && *a == arity)
+        .map(|(_, _, function)| *function)
+}
+
+pub fn find_format(name: &str) -> Option<Format> {
+    FORMATS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, format)| *format)
+}
+
+fn empty<'a>(_: &'a [Ast], _: &Env<'a>, _: Value) -> Outputs<'a> {
+    Box::new(iter::empty())
+}
+
+/// Raises the input as an error.
+fn error<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    Box::new(iter::once(Err(Error::Raised(json_data))))
+}
+
+fn error_with<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    flat_map(Evaluator::eval(&args[0], env, json_data), |message| {
+        Box::new(iter::once(Err(Error::Raised(message))))
+    })
+}
+
+fn length<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let length = match &json_data {
+        Value::Array(e) => e.len() as f64,
+        Value::Object(e) => e.len() as f64,
+        Value::String(e) => e.chars().count() as f64,
+        Value::Number(e) => e.as_f64().unwrap_or_default().abs(),
+        Value::Null => 0.0,
+        _ => {
+            return Box::new(iter::once(Err(Error::Type(format!(
+                "{} has no length",
+                type_id(&json_data)
+            )))))
+        }
+    };
+
+    once(number(length))
+}
+
+fn not<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    once((!truthy(&json_data)).into())
+}
+
+/// Emits the input once for every truthy output of the condition.
+fn select<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let input = json_data.clone();
+    Box::new(Evaluator::eval(&args[0], env, json_data).filter_map(
+        move |condition| match condition {
+            Ok(condition) => truthy(&condition).then(|| Ok(input.clone())),
+            Err(e) => Some(Err(e)),
+        },
+    ))
+}
+
+fn map<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let env = env.clone();
+    let values = flat_map(Evaluator::iterate(json_data), move |value| {
+        Evaluator::eval(&args[0], &env, value)
+    });
+    Box::new(iter::once(
+        values.collect::<Result<Vec<_>, _>>().map(Value::Array),
+    ))
+}
+
+/// Replaces every value with the first output of the filter, dropping values
+/// the filter produces nothing for.
+fn map_values<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let first = |value| Evaluator::eval(&args[0], env, value).next().transpose();
+
+    let mapped = match json_data {
+        Value::Array(values) => values
+            .into_iter()
+            .filter_map(|value| first(value).transpose())
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array),
+        Value::Object(values) => values
+            .into_iter()
+            .filter_map(|(key, value)| Some(first(value).transpose()?.map(|value| (key, value))))
+            .collect::<Result<Map<_, _>, _>>()
+            .map(Value::Object),
+        value => Err(Error::Type(format!(
+            "Cannot iterate over {}",
+            type_id(&value)
+        ))),
+    };
+
+    Box::new(iter::once(mapped))
+}
+
+/// Emits the input and then every value nested in it, parents first.
+fn recurse<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let nested = match &json_data {
+        Value::Array(_) | Value::Object(_) => {
+            flat_map(Evaluator::iterate(json_data.clone()), |value| {
+                recurse(&[], &Env::default(), value)
+            })
+        }
+        _ => Box::new(iter::empty()),
+    };
+
+    Box::new(iter::once(Ok(json_data)).chain(nested))
+}
+
+/// Emits the input and then recursively every output of the filter.
+fn recurse_with<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let env = env.clone();
+    let nested = flat_map(
+        Evaluator::eval(&args[0], &env, json_data.clone()),
+        move |value| recurse_with(args, &env, value),
+    );
+
+    Box::new(iter::once(Ok(json_data)).chain(nested))
+}
+
+/// Emits at most the first `n` outputs of the filter.
+fn limit<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let env = env.clone();
+    with_values(&args[..1], &env.clone(), json_data.clone(), move |values| {
+        let n = match &values[0] {
+            Value::Number(n) => limit_count(n.as_f64().unwrap_or_default()),
+            n => return type_error(format!("Invalid limit {}", type_id(n))),
+        };
+
+        Box::new(Evaluator::eval(&args[1], &env, json_data.clone()).take(n))
+    })
+}
+
+/// How many outputs `limit(n; f)` takes. A negative limit takes all of them,
+/// like jq does.
+fn limit_count(n: f64) -> usize {
+    if n < 0.0 {
+        return usize::MAX;
+    }
+
+    n.ceil() as usize
+}
+
+fn first<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    element(json_data, |values| values.first())
+}
+
+fn first_of<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    Box::new(Evaluator::eval(&args[0], env, json_data).take(1))
+}
+
+fn last<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    element(json_data, |values| values.last())
+}
+
+fn last_of<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let mut last = None;
+    for output in Evaluator::eval(&args[0], env, json_data) {
+        match output {
+            Ok(output) => last = Some(output),
+            Err(e) => return Box::new(iter::once(Err(e))),
+        }
+    }
+
+    Box::new(last.into_iter().map(Ok))
+}
+
+fn element<'a>(json_data: Value, f: impl Fn(&[Value]) -> Option<&Value>) -> Outputs<'a> {
+    match &json_data {
+        Value::Array(values) => once(f(values).cloned().unwrap_or_default()),
+        Value::Null => once(Value::Null),
+        value => type_error(format!("Cannot index {} with number", type_id(value))),
+    }
+}
+
+/// Applies the update until the condition holds, emitting the final value.
+fn until<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let env = env.clone();
+    flat_map(
+        Evaluator::eval(&args[0], &env, json_data.clone()),
+        move |condition| {
+            if truthy(&condition) {
+                return once(json_data.clone());
+            }
+
+            let env = env.clone();
+            flat_map(
+                Evaluator::eval(&args[1], &env, json_data.clone()),
+                move |value| until(args, &env, value),
+            )
+        },
+    )
+}
+
+/// Emits the input and every update of it while the condition holds.
+fn while_<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let env = env.clone();
+    flat_map(
+        Evaluator::eval(&args[0], &env, json_data.clone()),
+        move |condition| {
+            if !truthy(&condition) {
+                return Box::new(iter::empty());
+            }
+
+            let env = env.clone();
+            let updates = flat_map(
+                Evaluator::eval(&args[1], &env, json_data.clone()),
+                move |value| while_(args, &env, value),
+            );
+            Box::new(iter::once(Ok(json_data.clone())).chain(updates))
+        },
+    )
+}
+
+/// `range(upto)`, `range(from; upto)` and `range(from; upto; by)`. A step of
+/// zero emits nothing rather than repeating `from` forever.
+fn range<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    with_values(args, env, json_data, |values| {
+        let mut bounds = vec![];
+        for value in &values {
+            match value.as_f64() {
+                Some(bound) => bounds.push(bound),
+                None => return type_error(String::from("Range bounds must be numeric")),
+            }
+        }
+
+        let (from, upto, by) = match bounds[..] {
+            [upto] => (0.0, upto, 1.0),
+            [from, upto] => (from, upto, 1.0),
+            [from, upto, by] => (from, upto, by),
+            _ => unreachable!(),
+        };
+
+        let numbers = iter::successors(Some(from), move |n| Some(n + by))
+            .take_while(move |n| (by > 0.0 && *n < upto) || (by < 0.0 && *n > upto))
+            .map(|n| Ok(number(n)));
+        Box::new(numbers)
+    })
+}
+
+/// Emits the path of every value the filter selects.
+fn path<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    Box::new(
+        Evaluator::paths(&args[0], env, vec![], json_data)
+            .map(|output| output.map(|(path, _)| Value::Array(path))),
+    )
+}
+
+/// Emits the path of every value nested in the input.
+fn paths<'a>(_: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    nested_paths(env, json_data, |_| true)
+}
+
+/// Emits the paths of nested values the filter is truthy for.
+fn paths_of<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let env = env.clone();
+    flat_map(
+        recurse_paths(&[], &env.clone(), vec![], json_data),
+        move |(path, value)| {
+            if path.is_empty() {
+                return Box::new(iter::empty());
+            }
+
+            let path = Value::Array(path);
+            Box::new(
+                Evaluator::eval(&args[0], &env, value).filter_map(
+                    move |condition| match condition {
+                        Ok(condition) => truthy(&condition).then(|| Ok(path.clone())),
+                        Err(e) => Some(Err(e)),
+                    },
+                ),
+            )
+        },
+    )
+}
+
+/// Emits the paths of nested values that are neither arrays nor objects.
+fn leaf_paths<'a>(_: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    nested_paths(env, json_data, |value| {
+        !matches!(value, Value::Array(_) | Value::Object(_))
+    })
+}
+
+fn nested_paths<'a>(
+    env: &Env<'a>,
+    json_data: Value,
+    f: impl Fn(&Value) -> bool + 'a,
+) -> Outputs<'a> {
+    Box::new(
+        recurse_paths(&[], env, vec![], json_data).filter_map(move |output| match output {
+            Ok((path, value)) => (!path.is_empty() && f(&value)).then_some(Ok(Value::Array(path))),
+            Err(e) => Some(Err(e)),
+        }),
+    )
+}
+
+/// The value at a path, null where it doesn't exist.
+fn getpath<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let input = json_data.clone();
+    with_values(args, env, json_data, move |values| {
+        let value = to_path(&values[0]).and_then(|path| path::get(&input, &path));
+        Box::new(iter::once(value))
+    })
+}
+
+fn setpath<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let input = json_data.clone();
+    with_values(args, env, json_data, move |values| {
+        let value =
+            to_path(&values[0]).and_then(|path| path::set(input.clone(), &path, values[1].clone()));
+        Box::new(iter::once(value))
+    })
+}
+
+/// Removes the values at an array of paths.
+fn delpaths<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let input = json_data.clone();
+    with_values(args, env, json_data, move |values| {
+        let paths = match &values[0] {
+            Value::Array(paths) => paths.iter().map(to_path).collect::<Result<Vec<_>, _>>(),
+            _ => Err(Error::Type(String::from(
+                "Paths must be specified as an array",
+            ))),
+        };
+        Box::new(iter::once(
+            paths.and_then(|paths| path::delete(input.clone(), paths)),
+        ))
+    })
+}
+
+/// Removes every value the filter selects.
+fn del<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let paths = Evaluator::paths(&args[0], env, vec![], json_data.clone())
+        .map(|output| output.map(|(path, _)| path))
+        .collect::<Result<Vec<_>, _>>();
+    Box::new(iter::once(
+        paths.and_then(|paths| path::delete(json_data, paths)),
+    ))
+}
+
+/// Keeps only the values the filter selects, at the same paths.
+fn pick<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let mut picked = Value::Null;
+    for output in Evaluator::paths(&args[0], env, vec![], json_data) {
+        match output.and_then(|(path, value)| path::set(picked, &path, value)) {
+            Ok(value) => picked = value,
+            Err(e) => return Box::new(iter::once(Err(e))),
+        }
+    }
+
+    once(picked)
+}
+
+/// Turns an object into an array of `{key, value}` entries, arrays use their
+/// indices as keys.
+fn to_entries<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let entry = |key: Value, value| {
+        let mut entry = Map::new();
+        entry.insert(String::from("key"), key);
+        entry.insert(String::from("value"), value);
+        Value::Object(entry)
+    };
+
+    match json_data {
+        Value::Object(values) => once(
+            values
+                .into_iter()
+                .map(|(key, value)| entry(Value::String(key), value))
+                .collect(),
+        ),
+        Value::Array(values) => once(
+            (0..)
+                .map(Value::from)
+                .zip(values)
+                .map(|(key, value)| entry(key, value))
+                .collect(),
+        ),
+        value => type_error(format!("{} has no keys", type_id(&value))),
+    }
+}
+
+/// Builds an object from entries. Like jq, keys are also read from `k`,
+/// `name`, `Name`, `K` and `Key`, values from `v` and `Value`.
+fn from_entries<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let entries = match json_data {
+        Value::Array(entries) => entries,
+        value => return type_error(format!("Cannot iterate over {}", type_id(&value))),
+    };
+
+    let mut object = Map::new();
+    for entry in entries {
+        let entry = match entry {
+            Value::Object(entry) => entry,
+            entry => return type_error(format!("Cannot index {} with \"key\"", type_id(&entry))),
+        };
+
+        let key = ["key", "k", "name", "Name", "K", "Key"]
+            .iter()
+            .filter_map(|key| entry.get(*key))
+            .find(|key| truthy(key));
+        let key = match key {
+            Some(Value::String(key)) => key.clone(),
+            Some(key) => key.to_string(),
+            None => String::from("null"),
+        };
+
+        let value = ["value", "v", "Value"]
+            .iter()
+            .find_map(|value| entry.get(*value))
+            .cloned()
+            .unwrap_or_default();
+        object.insert(key, value);
+    }
+
+    once(Value::Object(object))
+}
+
+/// `to_entries | map(f) | from_entries`
+fn with_entries<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let env = env.clone();
+    flat_map(to_entries(&[], &env.clone(), json_data), move |entries| {
+        flat_map(map(args, &env, entries), |entries| {
+            from_entries(&[], &Env::default(), entries)
+        })
+    })
+}
+
+/// Keys of an object in order, or the indices of an array.
+fn keys<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    match json_data {
+        Value::Object(values) => once(
+            values
+                .into_iter()
+                .map(|(key, _)| Value::String(key))
+                .collect(),
+        ),
+        Value::Array(values) => once((0..values.len()).map(Value::from).collect()),
+        value => type_error(format!("{} has no keys", type_id(&value))),
+    }
+}
+
+/// Emits the input unless it's null.
+fn values<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    match json_data {
+        Value::Null => Box::new(iter::empty()),
+        value => once(value),
+    }
+}
+
+/// Whether an object has a key or an array has an index.
+fn has<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let input = json_data.clone();
+    with_values(args, env, json_data, move |values| {
+        Box::new(iter::once(has_key(&input, &values[0])))
+    })
+}
+
+/// Whether the input is a key of the object or an index of the array the
+/// filter produces.
+fn in_<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let key = json_data.clone();
+    with_values(args, env, json_data, move |values| {
+        Box::new(iter::once(has_key(&values[0], &key)))
+    })
+}
+
+fn has_key(value: &Value, key: &Value) -> Result<Value, Error> {
+    let has = match (value, key) {
+        (Value::Object(values), Value::String(key)) => values.contains_key(key),
+        (Value::Array(values), Value::Number(index)) => {
+            let index = index.as_f64().unwrap_or_default();
+            index >= 0.0 && index < values.len() as f64
+        }
+        (value, key) => {
+            return Err(Error::Type(format!(
+                "Cannot check whether {} has a key of {}",
+                type_id(value),
+                type_id(key)
+            )))
+        }
+    };
+
+    Ok(has.into())
+}
+
+fn contains<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let input = json_data.clone();
+    with_values(args, env, json_data, move |values| {
+        Box::new(iter::once(contained(&input, &values[0]).map(Value::from)))
+    })
+}
+
+fn inside<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let input = json_data.clone();
+    with_values(args, env, json_data, move |values| {
+        Box::new(iter::once(contained(&values[0], &input).map(Value::from)))
+    })
+}
+
+/// Whether `b` is contained in `a`: substrings of strings, every element of
+/// `b` within some element of `a` for arrays, and recursively by key for
+/// objects. Other values must be equal.
+fn contained(a: &Value, b: &Value) -> Result<bool, Error> {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (key, b) in b {
+                match a.get(key) {
+                    Some(a) if contained(a, b)? => {}
+                    _ => return Ok(false),
+                }
+            }
+
+            Ok(true)
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            for b in b {
+                if !a
+                    .iter()
+                    .try_fold(false, |found, a| Ok::<_, Error>(found || contained(a, b)?))?
+                {
+                    return Ok(false);
+                }
+            }
+
+            Ok(true)
+        }
+        (Value::String(a), Value::String(b)) => Ok(a.contains(b.as_str())),
+        (a, b) if type_name(a) == type_name(b) => Ok(compare(a, b) == Ordering::Equal),
+        (a, b) => Err(Error::Type(format!(
+            "{} and {} cannot have their containment checked",
+            type_id(a),
+            type_id(b)
+        ))),
+    }
+}
+
+/// Adds up the elements of an array, null when there are none.
+fn add<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let sum = Evaluator::iterate(json_data).try_fold(Value::Null, |sum, value| {
+        Operator::Addition.apply(sum, value?)
+    });
+    Box::new(iter::once(sum))
+}
+
+/// `any`, `any(condition)` and `any(generator; condition)`, stopping at the
+/// first truthy output.
+fn any<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    Box::new(iter::once(conditions(args, env, json_data).and_then(
+        |mut conditions| {
+            conditions
+                .try_fold(false, |_, condition| match condition {
+                    Ok(condition) if truthy(&condition) => Err(Ok(true)),
+                    Ok(_) => Ok(false),
+                    Err(e) => Err(Err(e)),
+                })
+                .or_else(|any| any)
+                .map(Value::from)
+        },
+    )))
+}
+
+/// `all`, `all(condition)` and `all(generator; condition)`, stopping at the
+/// first falsy output.
+fn all<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    Box::new(iter::once(conditions(args, env, json_data).and_then(
+        |mut conditions| {
+            conditions
+                .try_fold(true, |_, condition| match condition {
+                    Ok(condition) if !truthy(&condition) => Err(Ok(false)),
+                    Ok(_) => Ok(true),
+                    Err(e) => Err(Err(e)),
+                })
+                .or_else(|all| all)
+                .map(Value::from)
+        },
+    )))
+}
+
+/// Outputs of the condition `any` and `all` check, run on the elements of
+/// the input or on the outputs of the generator.
+fn conditions<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Result<Outputs<'a>, Error> {
+    let env = env.clone();
+    let (generator, condition) = match args {
+        [] => return Ok(Evaluator::iterate(json_data)),
+        [condition] => (Evaluator::iterate(json_data), condition),
+        [generator, condition] => (Evaluator::eval(generator, &env, json_data), condition),
+        _ => unreachable!(),
+    };
+
+    Ok(flat_map(generator, move |value| {
+        Evaluator::eval(condition, &env, value)
+    }))
+}
+
+/// Flattens nested arrays, at most `depth` levels deep when given.
+fn flatten<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    fn flat(values: Vec<Value>, depth: f64, flattened: &mut Vec<Value>) {
+        for value in values {
+            match value {
+                Value::Array(values) if depth > 0.0 => flat(values, depth - 1.0, flattened),
+                value => flattened.push(value),
+            }
+        }
+    }
+
+    let input = json_data.clone();
+    with_values(args, env, json_data, move |values| {
+        let depth = match values.first() {
+            Some(Value::Number(depth)) => depth.as_f64().unwrap_or_default(),
+            Some(depth) => return type_error(format!("Invalid flatten depth {}", type_id(depth))),
+            None => f64::INFINITY,
+        };
+
+        if depth < 0.0 {
+            return type_error(String::from("Flatten depth must not be negative"));
+        }
+
+        match input.clone() {
+            Value::Array(values) => {
+                let mut flattened = vec![];
+                flat(values, depth, &mut flattened);
+                once(Value::Array(flattened))
+            }
+            value => type_error(format!("Cannot flatten {}", type_id(&value))),
+        }
+    })
+}
+
+/// The smallest element of an array by jq ordering, the first of equals and
+/// null when empty. `min_by(f)` compares the outputs of `f` instead.
+fn min<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    extreme(keyed(args, env, json_data), Ordering::Less)
+}
+
+/// The largest element of an array by jq ordering, the last of equals and
+/// null when empty. `max_by(f)` compares the outputs of `f` instead.
+fn max<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    extreme(keyed(args, env, json_data), Ordering::Greater)
+}
+
+fn extreme<'a>(keyed: Result<Vec<(Value, Value)>, Error>, ordering: Ordering) -> Outputs<'a> {
+    let keyed = match keyed {
+        Ok(keyed) => keyed,
+        Err(e) => return Box::new(iter::once(Err(e))),
+    };
+
+    let extreme = keyed
+        .into_iter()
+        .reduce(|extreme, keyed| match compare(&keyed.0, &extreme.0) {
+            Ordering::Equal if ordering == Ordering::Greater => keyed,
+            found if found == ordering => keyed,
+            _ => extreme,
+        });
+    once(extreme.map(|(_, value)| value).unwrap_or_default())
+}
+
+/// Sorts an array by jq ordering, or by the outputs of `f` for `sort_by(f)`.
+/// Elements with equal keys keep their order.
+fn sort<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let sorted = sorted(args, env, json_data)
+        .map(|sorted| sorted.into_iter().map(|(_, value)| value).collect());
+    Box::new(iter::once(sorted))
+}
+
+/// Groups the elements of an array with equal outputs of `f`, sorted by them.
+fn group_by<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let groups =
+        grouped(args, env, json_data).map(|groups| groups.into_iter().map(Value::Array).collect());
+    Box::new(iter::once(groups))
+}
+
+/// The sorted elements of an array without duplicates, `unique_by(f)` keeps
+/// the first element of every group of equal outputs of `f`.
+fn unique<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let unique = grouped(args, env, json_data).map(|groups| {
+        groups
+            .into_iter()
+            .filter_map(|group| group.into_iter().next())
+            .collect()
+    });
+    Box::new(iter::once(unique))
+}
+
+/// Pairs every element of an array with its key, the outputs of `f` collected
+/// into an array or the element itself when there's no `f`.
+fn keyed<'a>(
+    args: &'a [Ast],
+    env: &Env<'a>,
+    json_data: Value,
+) -> Result<Vec<(Value, Value)>, Error> {
+    let values = match json_data {
+        Value::Array(values) => values,
+        value => {
+            return Err(Error::Type(format!(
+                "{} cannot be sorted, as it is not an array",
+                type_id(&value)
+            )))
+        }
+    };
+
+    values
+        .into_iter()
+        .map(|value| match args.first() {
+            Some(f) => {
+                let key = Evaluator::eval(f, env, value.clone()).collect::<Result<_, _>>()?;
+                Ok((Value::Array(key), value))
+            }
+            None => Ok((value.clone(), value)),
+        })
+        .collect()
+}
+
+fn sorted<'a>(
+    args: &'a [Ast],
+    env: &Env<'a>,
+    json_data: Value,
+) -> Result<Vec<(Value, Value)>, Error> {
+    let mut keyed = keyed(args, env, json_data)?;
+    keyed.sort_by(|(a, _), (b, _)| compare(a, b));
+    Ok(keyed)
+}
+
+fn grouped<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Result<Vec<Vec<Value>>, Error> {
+    let mut groups: Vec<(Value, Vec<Value>)> = vec![];
+    for (key, value) in sorted(args, env, json_data)? {
+        match groups.last_mut() {
+            Some((last, group)) if compare(last, &key) == Ordering::Equal => group.push(value),
+            _ => groups.push((key, vec![value])),
+        }
+    }
+
+    Ok(groups.into_iter().map(|(_, group)| group).collect())
+}
+
+fn reverse<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    match json_data {
+        Value::Array(values) => once(values.into_iter().rev().collect()),
+        Value::String(string) => once(Value::String(string.chars().rev().collect())),
+        Value::Null => once(Value::Array(vec![])),
+        value => type_error(format!("Cannot reverse {}", type_id(&value))),
+    }
+}
+
+/// Offsets of a substring, of an element or of a subarray.
+fn indices<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let input = json_data.clone();
+    with_values(args, env, json_data, move |values| {
+        let indices = positions(&input, &values[0]);
+        Box::new(iter::once(indices.map(|indices| match indices {
+            Some(indices) => indices.into_iter().map(Value::from).collect(),
+            None => Value::Null,
+        })))
+    })
+}
+
+/// The first offset `indices` finds, null when there is none.
+fn index<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let input = json_data.clone();
+    with_values(args, env, json_data, move |values| {
+        let index = positions(&input, &values[0]);
+        Box::new(iter::once(index.map(|indices| {
+            indices.and_then(|indices| indices.first().copied()).into()
+        })))
+    })
+}
+
+/// The last offset `indices` finds, null when there is none.
+fn rindex<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let input = json_data.clone();
+    with_values(args, env, json_data, move |values| {
+        let index = positions(&input, &values[0]);
+        Box::new(iter::once(index.map(|indices| {
+            indices.and_then(|indices| indices.last().copied()).into()
+        })))
+    })
+}
+
+/// Offsets of `needle` in `haystack`, none for a null haystack. Offsets in
+/// strings count characters.
+fn positions(haystack: &Value, needle: &Value) -> Result<Option<Vec<usize>>, Error> {
+    let positions = match (haystack, needle) {
+        (Value::Null, _) => return Ok(None),
+        (Value::String(haystack), Value::String(needle)) => {
+            let haystack: Vec<char> = haystack.chars().collect();
+            let needle: Vec<char> = needle.chars().collect();
+            match needle.is_empty() {
+                true => vec![],
+                false => haystack
+                    .windows(needle.len())
+                    .enumerate()
+                    .filter(|(_, window)| *window == needle.as_slice())
+                    .map(|(i, _)| i)
+                    .collect(),
+            }
+        }
+        (Value::Array(haystack), Value::Array(needle)) => match needle.is_empty() {
+            true => vec![],
+            false => haystack
+                .windows(needle.len())
+                .enumerate()
+                .filter(|(_, window)| *window == needle.as_slice())
+                .map(|(i, _)| i)
+                .collect(),
+        },
+        (Value::Array(haystack), needle) => haystack
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| *value == needle)
+            .map(|(i, _)| i)
+            .collect(),
+        (haystack, needle) => {
+            return Err(Error::Type(format!(
+                "Cannot determine the indices of {} in {}",
+                type_id(needle),
+                type_id(haystack)
+            )))
+        }
+    };
+
+    Ok(Some(positions))
+}
+
+/// Strings are kept as they are, other values become their JSON text.
+fn tostring<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    Box::new(iter::once(text(json_data).map(Value::String)))
+}
+
+fn text(value: Value) -> Result<String, Error> {
+    match value {
+        Value::String(string) => Ok(string),
+        value => match date::datetime(&value) {
+            Some(date) => Ok(String::from(date)),
+            None => Ok(value.to_string()),
+        },
+    }
+}
+
+/// Length of a string in bytes when encoded as UTF-8.
+fn utf8bytelength<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    match json_data {
+        Value::String(string) => once(Value::from(string.len())),
+        value => type_error(format!(
+            "{} only strings have UTF-8 byte length",
+            type_id(&value)
+        )),
+    }
+}
+
+/// Splits a string on a separator, an empty separator splits it into
+/// characters.
+fn split<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let input = json_data.clone();
+    with_values(args, env, json_data, move |values| {
+        let parts = match (&input, &values[0]) {
+            (Value::String(string), _) if string.is_empty() => vec![],
+            (Value::String(string), Value::String(separator)) if separator.is_empty() => string
+                .chars()
+                .map(|char| Value::String(char.into()))
+                .collect(),
+            (Value::String(string), Value::String(separator)) => {
+                string.split(separator.as_str()).map(Value::from).collect()
+            }
+            _ => return type_error(String::from("split input and separator must be strings")),
+        };
+
+        once(Value::Array(parts))
+    })
+}
+
+/// Emits the parts of a string between the matches of a regex.
+fn splits<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    flat_map(split_matches(args, env, json_data), Evaluator::iterate)
+}
+
+/// Joins the elements of an array with a separator. Nulls are empty, numbers
+/// and booleans are joined as their JSON text.
+fn join<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let input = json_data.clone();
+    with_values(args, env, json_data, move |values| {
+        let separator = match &values[0] {
+            Value::String(separator) => separator.clone(),
+            separator => return type_error(format!("Cannot join with {}", type_id(separator))),
+        };
+
+        let mut parts = vec![];
+        for value in Evaluator::iterate(input.clone()) {
+            match value {
+                Ok(Value::Null) => parts.push(String::new()),
+                Ok(Value::String(part)) => parts.push(part),
+                Ok(part @ (Value::Number(_) | Value::Bool(_))) => parts.push(part.to_string()),
+                Ok(part) => return type_error(format!("Cannot join {}", type_id(&part))),
+                Err(e) => return Box::new(iter::once(Err(e))),
+            }
+        }
+
+        once(Value::String(parts.join(&separator)))
+    })
+}
+
+fn ascii_downcase<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    map_string(json_data, "ascii_downcase", |string| {
+        string.to_ascii_lowercase()
+    })
+}
+
+fn ascii_upcase<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    map_string(json_data, "ascii_upcase", |string| {
+        string.to_ascii_uppercase()
+    })
+}
+
+fn trim<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    map_string(json_data, "trim", |string| string.trim().to_string())
+}
+
+fn ltrim<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    map_string(json_data, "ltrim", |string| string.trim_start().to_string())
+}
+
+fn rtrim<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    map_string(json_data, "rtrim", |string| string.trim_end().to_string())
+}
+
+fn map_string<'a>(json_data: Value, name: &str, f: impl Fn(&str) -> String) -> Outputs<'a> {
+    match json_data {
+        Value::String(string) => once(Value::String(f(&string))),
+        value => type_error(format!("{} cannot be used with {}", name, type_id(&value))),
+    }
+}
+
+/// Removes a prefix, inputs without it are emitted unchanged.
+fn ltrimstr<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let input = json_data.clone();
+    with_values(args, env, json_data, move |values| {
+        let trimmed = match (&input, &values[0]) {
+            (Value::String(string), Value::String(prefix)) => string.strip_prefix(prefix.as_str()),
+            _ => None,
+        };
+        once(trimmed.map(Value::from).unwrap_or_else(|| input.clone()))
+    })
+}
+
+/// Removes a suffix, inputs without it are emitted unchanged.
+fn rtrimstr<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let input = json_data.clone();
+    with_values(args, env, json_data, move |values| {
+        let trimmed = match (&input, &values[0]) {
+            (Value::String(string), Value::String(suffix)) => string.strip_suffix(suffix.as_str()),
+            _ => None,
+        };
+        once(trimmed.map(Value::from).unwrap_or_else(|| input.clone()))
+    })
+}
+
+fn startswith<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let input = json_data.clone();
+    with_values(args, env, json_data, move |values| {
+        match (&input, &values[0]) {
+            (Value::String(string), Value::String(prefix)) => {
+                once(string.starts_with(prefix.as_str()).into())
+            }
+            _ => type_error(String::from("startswith() requires string inputs")),
+        }
+    })
+}
+
+fn endswith<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let input = json_data.clone();
+    with_values(args, env, json_data, move |values| {
+        match (&input, &values[0]) {
+            (Value::String(string), Value::String(suffix)) => {
+                once(string.ends_with(suffix.as_str()).into())
+            }
+            _ => type_error(String::from("endswith() requires string inputs")),
+        }
+    })
+}
+
+/// The codepoints of a string.
+fn explode<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    match json_data {
+        Value::String(string) => once(
+            string
+                .chars()
+                .map(|char| Value::from(char as u32))
+                .collect(),
+        ),
+        value => type_error(format!("{} cannot be exploded", type_id(&value))),
+    }
+}
+
+/// The string of an array of codepoints.
+fn implode<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let codepoints = match json_data {
+        Value::Array(codepoints) => codepoints,
+        value => return type_error(format!("{} cannot be imploded", type_id(&value))),
+    };
+
+    let mut string = String::new();
+    for codepoint in codepoints {
+        match codepoint
+            .as_u64()
+            .and_then(|c| char::from_u32(c.try_into().ok()?))
+        {
+            Some(char) => string.push(char),
+            None => return type_error(format!("Invalid codepoint {}", type_id(&codepoint))),
+        }
+    }
+
+    once(Value::String(string))
+}
+
+/// The one character string of an ASCII codepoint.
+fn ascii<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    match json_data.as_u64().filter(|codepoint| *codepoint < 128) {
+        Some(codepoint) => once(Value::String(char::from(codepoint as u8).into())),
+        None => type_error(format!("{} is not an ASCII codepoint", type_id(&json_data))),
+    }
+}
+
+fn tojson<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    once(Value::String(json_data.to_string()))
+}
+
+fn fromjson<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    match json_data {
+        Value::String(string) => match serde_json::from_str(&string) {
+            Ok(value) => once(value),
+            Err(e) => type_error(format!("{} (while parsing {:?})", e, string)),
+        },
+        value => type_error(format!("{} cannot be parsed as JSON", type_id(&value))),
+    }
+}
+
+fn tonumber<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    match json_data {
+        Value::Number(number) => once(Value::Number(number)),
+        Value::String(string) => match string.trim().parse::<f64>() {
+            Ok(number) if number.is_finite() => once(self::number(number)),
+            _ => type_error(format!("Cannot parse {:?} as a number", string)),
+        },
+        value => type_error(format!("{} cannot be parsed as a number", type_id(&value))),
+    }
+}
+
+fn type_<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    once(Value::from(type_name(&json_data)))
+}
+
+/// A compiled regex with the flags that aren't part of it.
+struct Search {
+    regex: Regex,
+    /// `g`, every match rather than only the first.
+    global: bool,
+    /// `n`, empty matches are skipped.
+    skip_empty: bool,
+}
+
+impl Search {
+    /// Compiles `re` with `flags`, either a string of flag characters or
+    /// null. `re` may also be a `[re, flags]` array.
+    fn new(re: &Value, flags: &Value) -> Result<Self, Error> {
+        let (re, flags) = match (re, flags) {
+            (Value::Array(pair), Value::Null) if !pair.is_empty() && pair.len() <= 2 => {
+                (&pair[0], pair.get(1).unwrap_or(&Value::Null))
+            }
+            _ => (re, flags),
+        };
+
+        let re = match re {
+            Value::String(re) => re,
+            re => {
+                return Err(Error::Type(format!(
+                    "{} cannot be matched, as it is not a string",
+                    type_id(re)
+                )))
+            }
+        };
+        let flags = match flags {
+            Value::String(flags) => flags.as_str(),
+            Value::Null => "",
+            flags => return Err(Error::Type(format!("{} is not a string", type_id(flags)))),
+        };
+
+        let mut builder = RegexBuilder::new(&named_groups(re));
+        let (mut global, mut skip_empty) = (false, false);
+        for flag in flags.chars() {
+            match flag {
+                'g' => global = true,
+                'i' => {
+                    builder.case_insensitive(true);
+                }
+                'x' => {
+                    builder.ignore_whitespace(true);
+                }
+                's' => {
+                    builder.dot_matches_new_line(true);
+                }
+                'n' => skip_empty = true,
+                _ => {
+                    return Err(Error::Type(format!(
+                        "{} is not a valid modifier string",
+                        flags
+                    )))
+                }
+            }
+        }
+
+        match builder.build() {
+            Ok(regex) => Ok(Self {
+                regex,
+                global,
+                skip_empty,
+            }),
+            Err(e) => {
+                let e = e.to_string();
+                let reason = e.lines().last().unwrap_or_default();
+                Err(Error::Type(format!(
+                    "{:?} is not a valid regex: {}",
+                    re,
+                    reason.trim_start_matches("error: ")
+                )))
+            }
+        }
+    }
+
+    /// Match objects like jq's `match`, offsets and lengths count characters.
+    fn matches(&self, string: &str) -> Vec<Value> {
+        let chars = |bytes: &str| Value::from(bytes.chars().count());
+        let names: Vec<Option<&str>> = self.regex.capture_names().skip(1).collect();
+
+        let mut matches = vec![];
+        for captures in self.regex.captures_iter(string) {
+            let whole = captures.get(0).unwrap();
+            if self.skip_empty && whole.as_str().is_empty() {
+                continue;
+            }
+
+            let groups = names
+                .iter()
+                .enumerate()
+                .map(|(i, name)| {
+                    let (offset, length, text) = match captures.get(i + 1) {
+                        Some(group) => (
+                            chars(&string[..group.start()]),
+                            chars(group.as_str()),
+                            Value::from(group.as_str()),
+                        ),
+                        None => (Value::from(-1), Value::from(0), Value::Null),
+                    };
+                    let name = name.map(Value::from).unwrap_or_default();
+                    Value::Object(match_object(offset, length, text, Some(name)))
+                })
+                .collect();
+
+            let mut object = match_object(
+                chars(&string[..whole.start()]),
+                chars(whole.as_str()),
+                Value::from(whole.as_str()),
+                None,
+            );
+            object.insert(String::from("captures"), Value::Array(groups));
+            matches.push(Value::Object(object));
+
+            if !self.global {
+                break;
+            }
+        }
+
+        matches
+    }
+
+    /// Byte ranges of the matches `matches` reports, with their named captures
+    /// as an object.
+    fn replacements(&self, string: &str) -> Vec<(usize, usize, Value)> {
+        let mut replacements = vec![];
+        for captures in self.regex.captures_iter(string) {
+            let whole = captures.get(0).unwrap();
+            if self.skip_empty && whole.as_str().is_empty() {
+                continue;
+            }
+
+            let named = self
+                .regex
+                .capture_names()
+                .flatten()
+                .map(|name| {
+                    let text = captures.name(name).map(|group| Value::from(group.as_str()));
+                    (name.to_string(), text.unwrap_or_default())
+                })
+                .collect();
+            replacements.push((whole.start(), whole.end(), Value::Object(named)));
+
+            if !self.global {
+                break;
+            }
+        }
+
+        replacements
+    }
+}
+
+fn match_object(
+    offset: Value,
+    length: Value,
+    string: Value,
+    name: Option<Value>,
+) -> Map<String, Value> {
+    let mut object = Map::new();
+    object.insert(String::from("offset"), offset);
+    object.insert(String::from("length"), length);
+    object.insert(String::from("string"), string);
+    if let Some(name) = name {
+        object.insert(String::from("name"), name);
+    }
+    object
+}
+
+/// Rewrites jq's `(?<name>...)` groups to the `(?P<name>...)` syntax of the
+/// regex crate.
+fn named_groups(re: &str) -> String {
+    let is_name_start = |char: char| char.is_ascii_alphabetic() || char == '_';
+
+    let mut rewritten = String::with_capacity(re.len());
+    let mut chars = re.char_indices();
+    while let Some((i, char)) = chars.next() {
+        rewritten.push(char);
+        match char {
+            '\\' => rewritten.extend(chars.next().map(|(_, char)| char)),
+            '(' if re[i + 1..].starts_with("?<") && re[i + 3..].starts_with(is_name_start) => {
+                rewritten.push_str("?P<");
+                chars.nth(1);
+            }
+            _ => {}
+        }
+    }
+
+    rewritten
+}
+
+/// Calls `f` with the input string and a `Search` for every combination of
+/// the outputs of the regex and flags arguments.
+fn with_search<'a>(
+    re: &'a Ast,
+    flags: Option<&'a Ast>,
+    env: &Env<'a>,
+    json_data: Value,
+    f: impl Fn(&str, Search) -> Outputs<'a> + 'a,
+) -> Outputs<'a> {
+    let string = match &json_data {
+        Value::String(string) => string.clone(),
+        value => {
+            return type_error(format!(
+                "{} cannot be matched, as it is not a string",
+                type_id(value)
+            ))
+        }
+    };
+
+    let f = Rc::new(f);
+    let env = env.clone();
+    flat_map(Evaluator::eval(re, &env, json_data.clone()), move |re| {
+        let flags = match flags {
+            Some(flags) => Evaluator::eval(flags, &env, json_data.clone()),
+            None => once(Value::Null),
+        };
+        let (string, f) = (string.clone(), f.clone());
+        flat_map(flags, move |flags| match Search::new(&re, &flags) {
+            Ok(search) => f(&string, search),
+            Err(e) => Box::new(iter::once(Err(e))),
+        })
+    })
+}
+
+/// Whether the regex matches the input.
+fn test<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    with_search(&args[0], args.get(1), env, json_data, |string, search| {
+        once(search.regex.is_match(string).into())
+    })
+}
+
+/// Emits an object for every match with its offset, length, string and
+/// captures.
+fn match_<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    with_search(&args[0], args.get(1), env, json_data, |string, search| {
+        Box::new(search.matches(string).into_iter().map(Ok))
+    })
+}
+
+/// Emits an object of the named captures of every match.
+fn capture<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    with_search(&args[0], args.get(1), env, json_data, |string, search| {
+        let captures = search.replacements(string).into_iter();
+        Box::new(captures.map(|(_, _, captures)| Ok(captures)))
+    })
+}
+
+/// Emits every match, or the array of its captures when the regex has any.
+fn scan<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    with_search(
+        &args[0],
+        args.get(1),
+        env,
+        json_data,
+        |string, mut search| {
+            search.global = true;
+            let scanned = search.matches(string).into_iter().map(|found| {
+                let captures = match &found["captures"] {
+                    Value::Array(captures) if !captures.is_empty() => captures,
+                    _ => return Ok(found["string"].clone()),
+                };
+                Ok(captures
+                    .iter()
+                    .map(|capture| capture["string"].clone())
+                    .collect())
+            });
+            Box::new(scanned)
+        },
+    )
+}
+
+/// `split(re; flags)`, the parts of a string between the matches of a regex.
+fn split_matches<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    with_search(
+        &args[0],
+        args.get(1),
+        env,
+        json_data,
+        |string, mut search| {
+            search.global = true;
+            let mut parts = vec![];
+            let mut start = 0;
+            for (from, to, _) in search.replacements(string) {
+                parts.push(Value::from(&string[start..from]));
+                start = to;
+            }
+            parts.push(Value::from(&string[start..]));
+            once(Value::Array(parts))
+        },
+    )
+}
+
+/// Replaces the first match with the output of the replacement filter, which
+/// runs on an object of the named captures.
+fn sub<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    substitute(args, env, json_data, false)
+}
+
+/// Replaces every match like `sub`.
+fn gsub<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    substitute(args, env, json_data, true)
+}
+
+fn substitute<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value, global: bool) -> Outputs<'a> {
+    let env = env.clone();
+    with_search(
+        &args[0],
+        args.get(2),
+        &env.clone(),
+        json_data,
+        move |string, mut search| {
+            search.global |= global;
+            let replacements = search.replacements(string);
+            replace(
+                &args[1],
+                env.clone(),
+                Rc::from(string),
+                Rc::new(replacements),
+                0,
+                0,
+                String::new(),
+            )
+        },
+    )
+}
+
+/// Emits `replaced` followed by the rest of `string` from `start`, with the
+/// matches from `index` on replaced. Every output of the replacement filter
+/// gives its own result.
+fn replace<'a>(
+    replacement: &'a Ast,
+    env: Env<'a>,
+    string: Rc<str>,
+    replacements: Rc<Vec<(usize, usize, Value)>>,
+    index: usize,
+    start: usize,
+    replaced: String,
+) -> Outputs<'a> {
+    let (from, to, captures) = match replacements.get(index) {
+        Some(found) => found.clone(),
+        None => return once(Value::String(replaced + &string[start..])),
+    };
+
+    let prefix = replaced + &string[start..from];
+    flat_map(Evaluator::eval(replacement, &env, captures), move |value| {
+        let value = match value {
+            Value::String(value) => value,
+            value => return type_error(format!("{} cannot be added to a string", type_id(&value))),
+        };
+
+        let replaced = prefix.clone() + &value;
+        replace(
+            replacement,
+            env.clone(),
+            string.clone(),
+            replacements.clone(),
+            index + 1,
+            to,
+            replaced,
+        )
+    })
+}
+
+fn floor<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::floor)
+}
+
+fn ceil<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::ceil)
+}
+
+fn round<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::round)
+}
+
+fn trunc<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::trunc)
+}
+
+fn fabs<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::abs)
+}
+
+fn sqrt<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::sqrt)
+}
+
+fn cbrt<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::cbrt)
+}
+
+fn exp<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::exp)
+}
+
+fn exp2<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::exp2)
+}
+
+fn exp10<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, |n| 10f64.powf(n))
+}
+
+fn expm1<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::exp_m1)
+}
+
+fn log<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::ln)
+}
+
+fn log2<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::log2)
+}
+
+fn log10<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::log10)
+}
+
+fn log1p<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::ln_1p)
+}
+
+fn logb<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, exponent)
+}
+
+fn significand<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, mantissa)
+}
+
+fn sin<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::sin)
+}
+
+fn cos<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::cos)
+}
+
+fn tan<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::tan)
+}
+
+fn asin<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::asin)
+}
+
+fn acos<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::acos)
+}
+
+fn atan<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::atan)
+}
+
+fn sinh<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::sinh)
+}
+
+fn cosh<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::cosh)
+}
+
+fn tanh<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::tanh)
+}
+
+fn asinh<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::asinh)
+}
+
+fn acosh<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::acosh)
+}
+
+fn atanh<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math(json_data, f64::atanh)
+}
+
+fn pow<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math_with(args, env, json_data, f64::powf)
+}
+
+fn atan2<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math_with(args, env, json_data, f64::atan2)
+}
+
+fn fmin<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math_with(args, env, json_data, f64::min)
+}
+
+fn fmax<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math_with(args, env, json_data, f64::max)
+}
+
+fn fmod<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    math_with(args, env, json_data, |a, b| a % b)
+}
+
+/// Applies `f` to a number input.
+fn math<'a>(json_data: Value, f: fn(f64) -> f64) -> Outputs<'a> {
+    match json_data {
+        Value::Number(n) => once(number(f(n.as_f64().unwrap_or_default()))),
+        value => type_error(format!("{} number required", type_id(&value))),
+    }
+}
+
+/// Applies `f` to the numbers both arguments produce.
+fn math_with<'a>(
+    args: &'a [Ast],
+    env: &Env<'a>,
+    json_data: Value,
+    f: fn(f64, f64) -> f64,
+) -> Outputs<'a> {
+    with_values(args, env, json_data, move |values| {
+        match (&values[0], &values[1]) {
+            (Value::Number(a), Value::Number(b)) => {
+                let (a, b) = (
+                    a.as_f64().unwrap_or_default(),
+                    b.as_f64().unwrap_or_default(),
+                );
+                once(number(f(a, b)))
+            }
+            (a, b) => type_error(format!("{} and {} number required", type_id(a), type_id(b))),
+        }
+    })
+}
+
+/// The binary exponent of a number, `floor(log2(|n|))`.
+fn exponent(n: f64) -> f64 {
+    n.abs().log2().floor()
+}
+
+/// The mantissa of a number scaled into `[1, 2)`.
+fn mantissa(n: f64) -> f64 {
+    if n == 0.0 || !n.is_finite() {
+        return n;
+    }
+
+    n / 2f64.powi(exponent(n) as i32)
+}
+
+/// JSON numbers can't be infinite, so this is the largest finite number,
+/// which `isinfinite` doesn't count as infinite.
+fn infinite<'a>(_: &'a [Ast], _: &Env<'a>, _: Value) -> Outputs<'a> {
+    once(number(f64::INFINITY))
+}
+
+/// JSON numbers can't be NaN, so this is null like jq prints it, and
+/// `isnan` and `type` see a null.
+fn nan<'a>(_: &'a [Ast], _: &Env<'a>, _: Value) -> Outputs<'a> {
+    once(number(f64::NAN))
+}
+
+fn isinfinite<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    classify(json_data, f64::is_infinite)
+}
+
+fn isnan<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    classify(json_data, f64::is_nan)
+}
+
+fn isnormal<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    classify(json_data, f64::is_normal)
+}
+
+fn classify<'a>(json_data: Value, predicate: fn(f64) -> bool) -> Outputs<'a> {
+    match json_data {
+        Value::Number(n) => once(predicate(n.as_f64().unwrap_or_default()).into()),
+        value => type_error(format!("{} number required", type_id(&value))),
+    }
+}
+
+/// Seconds since the Unix epoch.
+fn now<'a>(_: &'a [Ast], _: &Env<'a>, _: Value) -> Outputs<'a> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH);
+    once(Value::from(now.map_or(0.0, |now| now.as_secs_f64())))
+}
+
+fn gmtime<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    match json_data {
+        Value::Number(n) => Box::new(iter::once(
+            Time::from_timestamp(n.as_f64().unwrap_or_default()).and_then(|time| time.to_value()),
+        )),
+        value => type_error(format!(
+            "{} cannot be broken down into a time, as it is not a number",
+            type_id(&value)
+        )),
+    }
+}
+
+fn mktime<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let timestamp = Time::from_value(&json_data).map(|time| number(time.timestamp().floor()));
+    Box::new(iter::once(timestamp))
+}
+
+fn strftime<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let input = json_data.clone();
+    with_values(args, env, json_data, move |values| match &values[0] {
+        Value::String(format) => Box::new(iter::once(
+            to_time(&input).and_then(|time| time.format(format).map(Value::from)),
+        )),
+        value => type_error(format!("{} is not a valid date format", type_id(value))),
+    })
+}
+
+fn strptime<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let input = json_data.clone();
+    with_values(args, env, json_data, move |values| {
+        let time = match (&input, &values[0]) {
+            (Value::String(string), Value::String(format)) => Time::parse(string, format),
+            (Value::String(_), value) => {
+                return type_error(format!("{} is not a valid date format", type_id(value)))
+            }
+            (value, _) => {
+                return type_error(format!(
+                    "{} cannot be parsed as a date, as it is not a string",
+                    type_id(value)
+                ))
+            }
+        };
+        Box::new(iter::once(time.and_then(|time| time.to_value())))
+    })
+}
+
+fn todate<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    let date = to_time(&json_data).and_then(|time| time.format(date::ISO8601));
+    Box::new(iter::once(date.map(Value::from)))
+}
+
+fn fromdate<'a>(_: &'a [Ast], _: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    match date::datetime(&json_data).or(json_data.as_str()) {
+        Some(date) => Box::new(iter::once(date::parse_iso8601(date).map(number))),
+        None => type_error(format!(
+            "{} cannot be parsed as a date, as it is not a string",
+            type_id(&json_data)
+        )),
+    }
+}
+
+/// `. + n` like jq's `dateadd`, the unit isn't used.
+fn dateadd<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    shift_date(args, env, json_data, Operator::Addition)
+}
+
+/// `. - n` like jq's `datesub`, the unit isn't used.
+fn datesub<'a>(args: &'a [Ast], env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+    shift_date(args, env, json_data, Operator::Subtration)
+}
+
+fn shift_date<'a>(
+    args: &'a [Ast],
+    env: &Env<'a>,
+    json_data: Value,
+    operator: Operator,
+) -> Outputs<'a> {
+    let input = json_data.clone();
+    with_values(&args[1..], env, json_data, move |values| {
+        Box::new(iter::once(operator.apply(input.clone(), values[0].clone())))
+    })
+}
+
+/// Reads a timestamp, a broken down time, a date string or a TOML datetime.
+fn to_time(value: &Value) -> Result<Time, Error> {
+    if let Some(date) = date::datetime(value) {
+        return date::parse_iso8601(date).and_then(Time::from_timestamp);
+    }
+
+    match value {
+        Value::Number(n) => Time::from_timestamp(n.as_f64().unwrap_or_default()),
+        Value::String(date) => date::parse_iso8601(date).and_then(Time::from_timestamp),
+        Value::Array(_) => Time::from_value(value),
+        value => Err(Error::Type(format!(
+            "{} is not a date, expected a timestamp, a broken down time or a date string",
+            type_id(value)
+        ))),
+    }
+}
+
+fn to_path(value: &Value) -> Result<Path, Error> {
+    match value {
+        Value::Array(path) => Ok(path.clone()),
+        _ => Err(Error::Type(String::from(
+            "Path must be specified as an array",
+        ))),
+    }
+}
+
+fn empty_paths<'a>(_: &'a [Ast], _: &Env<'a>, _: Path, _: Value) -> Paths<'a> {
+    Box::new(iter::empty())
+}
+
+fn error_paths<'a>(args: &'a [Ast], env: &Env<'a>, _: Path, json_data: Value) -> Paths<'a> {
+    let raised = match args.len() {
+        0 => error(args, env, json_data),
+        _ => error_with(args, env, json_data),
+    };
+    Box::new(raised.map(|value| value.map(|_| unreachable!())))
+}
+
+fn select_paths<'a>(args: &'a [Ast], env: &Env<'a>, path: Path, json_data: Value) -> Paths<'a> {
+    let input = (path, json_data.clone());
+    Box::new(Evaluator::eval(&args[0], env, json_data).filter_map(
+        move |condition| match condition {
+            Ok(condition) => truthy(&condition).then(|| Ok(input.clone())),
+            Err(e) => Some(Err(e)),
+        },
+    ))
+}
+
+/// `recurse` selects the input and every value nested in it, `recurse(f)`
+/// the input and recursively every path `f` selects.
+fn recurse_paths<'a>(args: &'a [Ast], env: &Env<'a>, path: Path, json_data: Value) -> Paths<'a> {
+    let children = match (args.first(), &json_data) {
+        (Some(f), _) => Evaluator::paths(f, env, path.clone(), json_data.clone()),
+        (None, Value::Array(_) | Value::Object(_)) => {
+            Evaluator::iterate_paths(path.clone(), json_data.clone())
+        }
+        (None, _) => Box::new(iter::empty()),
+    };
+
+    let env = env.clone();
+    let nested = flat_map(children, move |(path, value)| {
+        recurse_paths(args, &env, path, value)
+    });
+
+    Box::new(iter::once(Ok((path, json_data))).chain(nested))
+}
+
+fn limit_paths<'a>(args: &'a [Ast], env: &Env<'a>, path: Path, json_data: Value) -> Paths<'a> {
+    let env = env.clone();
+    flat_map(
+        Evaluator::eval(&args[0], &env.clone(), json_data.clone()),
+        move |n| {
+            let n = match &n {
+                Value::Number(n) => limit_count(n.as_f64().unwrap_or_default()),
+                n => return path_error(format!("Invalid limit {}", type_id(n))),
+            };
+
+            Box::new(Evaluator::paths(&args[1], &env, path.clone(), json_data.clone()).take(n))
+        },
+    )
+}
+
+fn first_paths<'a>(args: &'a [Ast], env: &Env<'a>, path: Path, json_data: Value) -> Paths<'a> {
+    match args.first() {
+        Some(f) => Box::new(Evaluator::paths(f, env, path, json_data).take(1)),
+        None => element_path(path, json_data, 0),
+    }
+}
+
+fn last_paths<'a>(args: &'a [Ast], env: &Env<'a>, path: Path, json_data: Value) -> Paths<'a> {
+    let f = match args.first() {
+        Some(f) => f,
+        None => return element_path(path, json_data, -1),
+    };
+
+    let mut last = None;
+    for output in Evaluator::paths(f, env, path, json_data) {
+        match output {
+            Ok(output) => last = Some(output),
+            Err(e) => return Box::new(iter::once(Err(e))),
+        }
+    }
+
+    Box::new(last.into_iter().map(Ok))
+}
+
+fn getpath_paths<'a>(args: &'a [Ast], env: &Env<'a>, path: Path, json_data: Value) -> Paths<'a> {
+    let env = env.clone();
+    flat_map(
+        Evaluator::eval(&args[0], &env, json_data.clone()),
+        move |nested| {
+            let nested = to_path(&nested);
+            let value = nested.and_then(|nested| {
+                let value = path::get(&json_data, &nested)?;
+                Ok(([path.clone(), nested].concat(), value))
+            });
+            Box::new(iter::once(value))
+        },
+    )
+}
+
+fn element_path<'a>(path: Path, json_data: Value, index: i64) -> Paths<'a> {
+    let index = Value::from(index);
+    let value = Evaluator::index(json_data, index.clone());
+    Box::new(iter::once(
+        value.map(|value| (path::append(&path, index), value)),
+    ))
+}
+
+/// Calls `f` with the values of `args`, once for every combination of their
+/// outputs. The first argument varies slowest.
+fn with_values<'a>(
+    args: &'a [Ast],
+    env: &Env<'a>,
+    json_data: Value,
+    f: impl Fn(Vec<Value>) -> Outputs<'a> + 'a,
+) -> Outputs<'a> {
+    fn product<'a>(
+        args: &'a [Ast],
+        env: Env<'a>,
+        json_data: Value,
+        values: Vec<Value>,
+        f: Rc<dyn Fn(Vec<Value>) -> Outputs<'a> + 'a>,
+    ) -> Outputs<'a> {
+        let (arg, rest) = match args.split_first() {
+            Some(arg) => arg,
+            None => return f(values),
+        };
+
+        flat_map(
+            Evaluator::eval(arg, &env, json_data.clone()),
+            move |value| {
+                let mut values = values.clone();
+                values.push(value);
+                product(rest, env.clone(), json_data.clone(), values, f.clone())
+            },
+        )
+    }
+
+    product(args, env.clone(), json_data, vec![], Rc::new(f))
+}
+
+/// Rewrites integral numbers nested anywhere in `value` as integers, so
+/// that strings built from them read `2` rather than `2.0`.
+pub fn integral(value: Value) -> Value {
+    match value {
+        Value::Number(n) if n.is_f64() => number(n.as_f64().unwrap_or_default()),
+        Value::Array(values) => values.into_iter().map(integral).collect(),
+        Value::Object(values) => Value::Object(
+            values
+                .into_iter()
+                .map(|(key, value)| (key, integral(value)))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+fn type_error<'a>(message: String) -> Outputs<'a> {
+    Box::new(iter::once(Err(Error::Type(message))))
+}
+
+fn path_error<'a>(message: String) -> Paths<'a> {
+    Box::new(iter::once(Err(Error::Type(message))))
+}
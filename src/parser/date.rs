@@ -0,0 +1,597 @@
+use super::operator::{number, type_id};
+use crate::{toml::DATETIME, Error};
+use serde_json::Value;
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const WEEKDAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+/// Format of `todate` and of the dates `fromdate` reads back.
+pub const ISO8601: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+/// Times are kept within a billion years of the epoch, far enough for any
+/// date while the calendar arithmetic can't overflow.
+const MAX_YEARS: f64 = 1e9;
+const MAX_TIMESTAMP: f64 = MAX_YEARS * 31556952.0;
+
+/// A broken down UTC time. Its array form, used by `gmtime` and `mktime`,
+/// is `[year, month, day, hours, minutes, seconds, weekday, yearday]` with
+/// months, weekdays and yeardays counting from 0 like C's `struct tm`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Time {
+    pub year: i64,
+    pub month: i64,
+    pub day: i64,
+    pub hour: i64,
+    pub minute: i64,
+    pub second: f64,
+}
+
+impl Time {
+    pub fn from_timestamp(timestamp: f64) -> Result<Time, Error> {
+        if timestamp.is_nan() || timestamp.abs() > MAX_TIMESTAMP {
+            return Err(Error::Type(format!(
+                "{} is out of range for a timestamp",
+                number(timestamp)
+            )));
+        }
+
+        let days = (timestamp / 86400.0).floor();
+        let seconds = timestamp - days * 86400.0;
+        let (year, month, day) = civil_from_days(days as i64);
+        let hour = (seconds / 3600.0).floor();
+        let minute = ((seconds - hour * 3600.0) / 60.0).floor();
+
+        Ok(Time {
+            year,
+            month: month - 1,
+            day,
+            hour: hour as i64,
+            minute: minute as i64,
+            second: seconds - hour * 3600.0 - minute * 60.0,
+        })
+    }
+
+    /// Seconds since the Unix epoch. Fields out of their range carry over
+    /// like they do for C's `timegm`, so the 32nd of January is the 1st of
+    /// February.
+    pub fn timestamp(&self) -> f64 {
+        let year = self.year + self.month.div_euclid(12);
+        let month = self.month.rem_euclid(12) + 1;
+        let days = days_from_civil(year, month, 1) + self.day - 1;
+
+        (days * 86400 + self.hour * 3600 + self.minute * 60) as f64 + self.second
+    }
+
+    /// Reads the array form, which needs at least the first six numbers.
+    /// Each of them has to be within a billion of 0.
+    pub fn from_value(value: &Value) -> Result<Time, Error> {
+        let fields = match value {
+            Value::Array(fields) if fields.len() >= 6 => fields
+                .iter()
+                .take(6)
+                .map(Value::as_f64)
+                .collect::<Option<Vec<_>>>(),
+            _ => None,
+        };
+
+        if let Some(field) = fields
+            .iter()
+            .flatten()
+            .find(|field| field.is_nan() || field.abs() > MAX_YEARS)
+        {
+            return Err(Error::Type(format!(
+                "{} is out of range for a broken down time",
+                number(*field)
+            )));
+        }
+
+        match fields.as_deref() {
+            Some(&[year, month, day, hour, minute, second]) => Ok(Time {
+                year: year as i64,
+                month: month as i64,
+                day: day as i64,
+                hour: hour as i64,
+                minute: minute as i64,
+                second,
+            }),
+            _ => Err(Error::Type(format!(
+                "{} is not a broken down time, expected an array of 6 numbers",
+                type_id(value)
+            ))),
+        }
+    }
+
+    pub fn to_value(&self) -> Result<Value, Error> {
+        let time = Time::from_timestamp(self.timestamp())?;
+        Ok(Value::Array(vec![
+            Value::from(time.year),
+            Value::from(time.month),
+            Value::from(time.day),
+            Value::from(time.hour),
+            Value::from(time.minute),
+            number(time.second),
+            Value::from(time.weekday()),
+            Value::from(time.yearday()),
+        ]))
+    }
+
+    /// Days since Sunday.
+    fn weekday(&self) -> i64 {
+        (self.days() + 4).rem_euclid(7)
+    }
+
+    /// Days since the 1st of January.
+    fn yearday(&self) -> i64 {
+        self.days() - days_from_civil(self.year, 1, 1)
+    }
+
+    fn days(&self) -> i64 {
+        (self.timestamp() / 86400.0).floor() as i64
+    }
+
+    /// Formats the time like C's `strftime`.
+    pub fn format(&self, format: &str) -> Result<String, Error> {
+        let time = Time::from_timestamp(self.timestamp())?;
+        let mut formatted = String::new();
+        let mut chars = format.chars();
+        while let Some(char) = chars.next() {
+            if char != '%' {
+                formatted.push(char);
+                continue;
+            }
+
+            let hour12 = (time.hour + 11) % 12 + 1;
+            let conversion = match chars.next() {
+                Some('Y') => time.year.to_string(),
+                Some('C') => format!("{:02}", time.year.div_euclid(100)),
+                Some('y') => format!("{:02}", time.year.rem_euclid(100)),
+                Some('m') => format!("{:02}", time.month + 1),
+                Some('d') => format!("{:02}", time.day),
+                Some('e') => format!("{:2}", time.day),
+                Some('H') => format!("{:02}", time.hour),
+                Some('I') => format!("{:02}", hour12),
+                Some('p') => String::from(if time.hour < 12 { "AM" } else { "PM" }),
+                Some('M') => format!("{:02}", time.minute),
+                Some('S') => format!("{:02}", time.second.floor()),
+                Some('j') => format!("{:03}", time.yearday() + 1),
+                Some('a') => WEEKDAYS[time.weekday() as usize][..3].to_string(),
+                Some('A') => WEEKDAYS[time.weekday() as usize].to_string(),
+                Some('b' | 'h') => MONTHS[time.month as usize][..3].to_string(),
+                Some('B') => MONTHS[time.month as usize].to_string(),
+                Some('u') => ((time.weekday() + 6) % 7 + 1).to_string(),
+                Some('w') => time.weekday().to_string(),
+                Some('s') => time.timestamp().floor().to_string(),
+                Some('Z') => String::from("UTC"),
+                Some('z') => String::from("+0000"),
+                Some('T') => time.format("%H:%M:%S")?,
+                Some('D') => time.format("%m/%d/%y")?,
+                Some('F') => time.format("%Y-%m-%d")?,
+                Some('R') => time.format("%H:%M")?,
+                Some('c') => time.format("%a %b %e %H:%M:%S %Y")?,
+                Some('n') => String::from("\n"),
+                Some('t') => String::from("\t"),
+                Some('%') => String::from("%"),
+                conversion => return Err(unsupported_conversion(conversion)),
+            };
+            formatted.push_str(&conversion);
+        }
+
+        Ok(formatted)
+    }
+
+    /// Parses `string` like C's `strptime`. Times with a `%z` offset are
+    /// converted to UTC.
+    pub fn parse(string: &str, format: &str) -> Result<Time, Error> {
+        let mut scanner = Scanner::new(string);
+        let mut time = Time::from_timestamp(0.0)?;
+        let mut offset = 0;
+        let mut afternoon = None;
+
+        if scanner.parse(format, &mut time, &mut offset, &mut afternoon)? && scanner.is_done() {
+            match afternoon {
+                Some(true) => time.hour = time.hour % 12 + 12,
+                Some(false) => time.hour %= 12,
+                None => {}
+            }
+
+            return Time::from_timestamp(time.timestamp() - offset as f64);
+        }
+
+        Err(Error::Type(format!(
+            "date {:?} does not match format {:?}",
+            string, format
+        )))
+    }
+}
+
+/// The string of a TOML datetime, which filters see wrapped in an object.
+pub fn datetime(value: &Value) -> Option<&str> {
+    match value {
+        Value::Object(values) if values.len() == 1 => values.get(DATETIME)?.as_str(),
+        _ => None,
+    }
+}
+
+/// Parses an RFC 3339 date like `2015-03-05T23:51:47Z`, including the ones
+/// TOML has: the time, its fractional seconds and the offset are optional
+/// and the time may be separated by a space. Dates without an offset are
+/// taken as UTC. Fields out of their range are rejected rather than
+/// carried over.
+pub fn parse_iso8601(string: &str) -> Result<f64, Error> {
+    let mut scanner = Scanner::new(string);
+    let invalid = || {
+        Error::Type(format!(
+            "date {:?} is not an RFC 3339 date like \"2015-03-05\" or \"2015-03-05T23:51:47Z\"",
+            string
+        ))
+    };
+
+    let mut time = Time::from_timestamp(0.0)?;
+    let mut offset = 0;
+    if !scanner.parse("%Y-%m-%d", &mut time, &mut offset, &mut None)? {
+        return Err(invalid());
+    }
+
+    if scanner.eat('T') || scanner.eat('t') || scanner.eat(' ') {
+        if !scanner.parse("%H:%M:%S", &mut time, &mut offset, &mut None)? {
+            return Err(invalid());
+        }
+
+        if scanner.eat('.') {
+            let digits = scanner.digits(9).ok_or_else(invalid)?;
+            time.second += format!("0.{}", digits).parse::<f64>().unwrap_or_default();
+        }
+
+        if !scanner.is_done() && !scanner.parse("%z", &mut time, &mut offset, &mut None)? {
+            return Err(invalid());
+        }
+    }
+
+    // A second of 60 is a leap second.
+    let in_range = (0..12).contains(&time.month)
+        && (1..=days_in_month(time.year, time.month + 1)).contains(&time.day)
+        && (0..24).contains(&time.hour)
+        && (0..60).contains(&time.minute)
+        && time.second < 61.0
+        && offset.abs() < 86400;
+    if !scanner.is_done() || !in_range {
+        return Err(invalid());
+    }
+
+    Ok(time.timestamp() - offset as f64)
+}
+
+/// Reads the input of `strptime` and `fromdate` one conversion at a time.
+struct Scanner<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(input: &'a str) -> Self {
+        Self { input, position: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    fn is_done(&self) -> bool {
+        self.position == self.input.len()
+    }
+
+    fn eat(&mut self, char: char) -> bool {
+        if self.rest().starts_with(char) {
+            self.position += char.len_utf8();
+            return true;
+        }
+
+        false
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    /// Up to `max` ASCII digits, at least one.
+    fn digits(&mut self, max: usize) -> Option<&'a str> {
+        let rest = self.rest();
+        let length = rest
+            .bytes()
+            .take(max)
+            .take_while(u8::is_ascii_digit)
+            .count();
+        if length == 0 {
+            return None;
+        }
+
+        self.position += length;
+        Some(&rest[..length])
+    }
+
+    fn number(&mut self, max: usize) -> Option<i64> {
+        let negative = self.eat('-');
+        let number = self.digits(max)?.parse::<i64>().ok()?;
+        Some(if negative { -number } else { number })
+    }
+
+    /// Index of the name, or of its three letter abbreviation, the input
+    /// starts with.
+    fn name(&mut self, names: &[&str]) -> Option<usize> {
+        let rest = self.rest().to_ascii_lowercase();
+        names.iter().position(|name| {
+            let name = name.to_ascii_lowercase();
+            let length = if rest.starts_with(&name) {
+                name.len()
+            } else if rest.starts_with(&name[..name.len().min(3)]) {
+                3
+            } else {
+                return false;
+            };
+
+            self.position += length;
+            true
+        })
+    }
+
+    /// Matches the input against `format`, filling in `time`. Returns false
+    /// when the input doesn't match.
+    fn parse(
+        &mut self,
+        format: &str,
+        time: &mut Time,
+        offset: &mut i64,
+        afternoon: &mut Option<bool>,
+    ) -> Result<bool, Error> {
+        let mut chars = format.chars();
+        while let Some(char) = chars.next() {
+            if char.is_whitespace() {
+                self.skip_whitespace();
+                continue;
+            }
+
+            if char != '%' {
+                if !self.eat(char) {
+                    return Ok(false);
+                }
+                continue;
+            }
+
+            let conversion = chars.next();
+            if matches!(conversion, Some('e' | 'n' | 't')) {
+                self.skip_whitespace();
+            }
+
+            let matched = match conversion {
+                Some('Y') => self.number(4).map(|year| time.year = year),
+                Some('y') => self
+                    .digits(2)
+                    .and_then(|year| year.parse::<i64>().ok())
+                    .map(|year| time.year = if year < 69 { 2000 + year } else { 1900 + year }),
+                Some('m') => self
+                    .digits(2)
+                    .and_then(|m| m.parse::<i64>().ok())
+                    .map(|month| {
+                        time.month = month - 1;
+                    }),
+                Some('d' | 'e') => self.parse_field(2, &mut time.day),
+                Some('H' | 'I') => self.parse_field(2, &mut time.hour),
+                Some('M') => self.parse_field(2, &mut time.minute),
+                Some('S') => self
+                    .digits(2)
+                    .and_then(|s| s.parse::<f64>().ok())
+                    .map(|second| {
+                        time.second = second;
+                    }),
+                Some('j') => self
+                    .digits(3)
+                    .and_then(|d| d.parse::<i64>().ok())
+                    .map(|day| {
+                        time.month = 0;
+                        time.day = day;
+                    }),
+                Some('s') => match self.number(20) {
+                    Some(seconds) => {
+                        *time = Time::from_timestamp(seconds as f64)?;
+                        Some(())
+                    }
+                    None => None,
+                },
+                Some('b' | 'B' | 'h') => self.name(&MONTHS).map(|month| {
+                    time.month = month as i64;
+                }),
+                Some('a' | 'A') => self.name(&WEEKDAYS).map(|_| ()),
+                Some('p') => self
+                    .name(&["AM", "PM"])
+                    .map(|pm| *afternoon = Some(pm == 1)),
+                Some('z') => self.offset().map(|seconds| *offset = seconds),
+                Some('Z') => {
+                    let rest = self.rest();
+                    let length = rest.len() - rest.trim_start_matches(char::is_alphabetic).len();
+                    self.position += length;
+                    (length > 0).then_some(())
+                }
+                Some('T') => self
+                    .parse("%H:%M:%S", time, offset, afternoon)?
+                    .then_some(()),
+                Some('D') => self
+                    .parse("%m/%d/%y", time, offset, afternoon)?
+                    .then_some(()),
+                Some('F') => self
+                    .parse("%Y-%m-%d", time, offset, afternoon)?
+                    .then_some(()),
+                Some('R') => self.parse("%H:%M", time, offset, afternoon)?.then_some(()),
+                Some('n' | 't') => Some(()),
+                Some('%') => self.eat('%').then_some(()),
+                conversion => return Err(unsupported_conversion(conversion)),
+            };
+
+            if matched.is_none() {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn parse_field(&mut self, max: usize, field: &mut i64) -> Option<()> {
+        *field = self.digits(max)?.parse().ok()?;
+        Some(())
+    }
+
+    /// A `Z` or an offset like `+0100` or `-07:30`, in seconds.
+    fn offset(&mut self) -> Option<i64> {
+        if self.eat('Z') || self.eat('z') {
+            return Some(0);
+        }
+
+        let sign = if self.eat('+') {
+            1
+        } else if self.eat('-') {
+            -1
+        } else {
+            return None;
+        };
+
+        let hours: i64 = self.digits(2)?.parse().ok()?;
+        self.eat(':');
+        let minutes: i64 = self.digits(2)?.parse().ok()?;
+        Some(sign * (hours * 3600 + minutes * 60))
+    }
+}
+
+fn unsupported_conversion(conversion: Option<char>) -> Error {
+    match conversion {
+        Some(conversion) => Error::Type(format!("%{} is not a supported conversion", conversion)),
+        None => Error::Type(String::from("Date formats can't end with a lone %")),
+    }
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days from 1970-01-01 to the given date of the proleptic Gregorian
+/// calendar, months counting from 1.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+/// The date `days` after 1970-01-01 as year, month and day, the inverse of
+/// `days_from_civil`.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400;
+
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+mod test_date {
+    #[test]
+    fn test_calendar() {
+        use super::*;
+
+        let tests = [
+            (0, (1970, 1, 1)),
+            (-1, (1969, 12, 31)),
+            (11016, (2000, 2, 29)),
+            (16499, (2015, 3, 5)),
+            (-719468, (0, 3, 1)),
+        ];
+
+        for (index, (days, date)) in tests.into_iter().enumerate() {
+            assert_eq!(
+                civil_from_days(days),
+                date,
+                "Failed testing index {}",
+                index
+            );
+            let (year, month, day) = date;
+            assert_eq!(
+                days_from_civil(year, month, day),
+                days,
+                "Failed testing index {}",
+                index
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_iso8601() {
+        use super::*;
+
+        let tests = [
+            ("2015-03-05T23:51:47Z", 1425599507.0),
+            ("2015-03-05t23:51:47z", 1425599507.0),
+            ("2015-03-05 23:51:47", 1425599507.0),
+            ("2015-03-06T00:51:47+01:00", 1425599507.0),
+            ("2015-03-05T23:51:47.5Z", 1425599507.5),
+            ("2015-03-05", 1425513600.0),
+        ];
+
+        for (index, (date, timestamp)) in tests.into_iter().enumerate() {
+            let result = parse_iso8601(date).unwrap();
+            assert_eq!(result, timestamp, "Failed testing index {}", index);
+        }
+
+        assert!(parse_iso8601("2015-03-05T23:51").is_err());
+        assert!(parse_iso8601("23:51:47").is_err());
+        assert!(parse_iso8601("2015-03-05Z").is_err());
+        assert!(parse_iso8601("2015-13-45").is_err());
+        assert!(parse_iso8601("2015-02-29").is_err());
+        assert!(parse_iso8601("2015-03-05T24:00:00Z").is_err());
+        assert!(parse_iso8601("2015-03-05T23:60:00Z").is_err());
+        assert!(parse_iso8601("2015-03-05T23:59:61Z").is_err());
+        assert!(parse_iso8601("2016-02-29T23:59:60Z").is_ok());
+    }
+}
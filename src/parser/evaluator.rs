@@ -0,0 +1,827 @@
+use super::ast::{Assignment, Ast, Definition, Fragment, Param, Pattern};
+use super::builtin::{self, Format};
+use super::operator::{negate, type_id};
+use super::path::{self, Path, Paths};
+use crate::Error;
+use serde_json::{Map, Value};
+use std::{iter, rc::Rc};
+
+/// Lazily evaluated results. A stream ends at its first error.
+pub type Stream<'a, T> = Box<dyn Iterator<Item = Result<T, Error>> + 'a>;
+
+/// Outputs of a filter.
+pub type Outputs<'a> = Stream<'a, Value>;
+
+/// Body of a binding, run in every scope the binding creates.
+type Body<'a, T> = Rc<dyn Fn(&Env<'a>) -> Stream<'a, T> + 'a>;
+
+/// Variables and functions in scope, the innermost binding first. Cloning
+/// is cheap, so every lazily evaluated output keeps its own copy.
+#[derive(Clone, Default)]
+pub struct Env<'a>(Option<Rc<Binding<'a>>>);
+
+struct Binding<'a> {
+    name: &'a str,
+    value: Bound<'a>,
+    parent: Env<'a>,
+}
+
+enum Bound<'a> {
+    Variable(Value),
+    Callable(Callable<'a>),
+}
+
+/// A function together with the scope it was defined in.
+#[derive(Clone)]
+pub enum Callable<'a> {
+    Definition(&'a Definition, Env<'a>),
+    /// Filter argument of a call, it takes no arguments itself.
+    Closure(&'a Ast, Env<'a>),
+}
+
+impl<'a> Callable<'a> {
+    fn arity(&self) -> usize {
+        match self {
+            Callable::Definition(definition, _) => definition.params.len(),
+            Callable::Closure(..) => 0,
+        }
+    }
+}
+
+impl<'a> Env<'a> {
+    pub fn bind(&self, name: &'a str, value: Value) -> Self {
+        self.push(name, Bound::Variable(value))
+    }
+
+    pub fn define(&self, name: &'a str, callable: Callable<'a>) -> Self {
+        self.push(name, Bound::Callable(callable))
+    }
+
+    fn push(&self, name: &'a str, value: Bound<'a>) -> Self {
+        Env(Some(Rc::new(Binding {
+            name,
+            value,
+            parent: self.clone(),
+        })))
+    }
+
+    fn bindings(&self) -> impl Iterator<Item = &Binding<'a>> {
+        iter::successors(self.0.as_deref(), |binding| binding.parent.0.as_deref())
+    }
+
+    pub fn variable(&self, name: &str) -> Option<&Value> {
+        self.bindings().find_map(|binding| match &binding.value {
+            Bound::Variable(value) if binding.name == name => Some(value),
+            _ => None,
+        })
+    }
+
+    pub fn callable(&self, name: &str, arity: usize) -> Option<&Callable<'a>> {
+        self.bindings().find_map(|binding| match &binding.value {
+            Bound::Callable(callable) if binding.name == name && callable.arity() == arity => {
+                Some(callable)
+            }
+            _ => None,
+        })
+    }
+}
+
+pub struct Evaluator;
+
+impl Evaluator {
+    pub fn eval<'a>(ast: &'a Ast, env: &Env<'a>, json_data: Value) -> Outputs<'a> {
+        let env = env.clone();
+        match ast {
+            Ast::Identity => once(json_data),
+            Ast::Field(target, key) => {
+                flat_map(Self::eval(target, &env, json_data), move |value| {
+                    Box::new(iter::once(Self::index(value, Value::String(key.clone()))))
+                })
+            }
+            Ast::Index(target, index) => {
+                flat_map(Self::eval(index, &env, json_data.clone()), move |index| {
+                    flat_map(Self::eval(target, &env, json_data.clone()), move |value| {
+                        Box::new(iter::once(Self::index(value, index.clone())))
+                    })
+                })
+            }
+            Ast::Iterate(target) => flat_map(Self::eval(target, &env, json_data), Self::iterate),
+            Ast::Literal(value) => once(value.clone()),
+            Ast::Variable(name) => match env.variable(name) {
+                Some(value) => once(value.clone()),
+                None => Box::new(iter::once(Err(Error::Type(format!(
+                    "${} is not defined",
+                    name
+                ))))),
+            },
+            Ast::Collect(ast) => {
+                let array = Self::eval(ast, &env, json_data).collect::<Result<Vec<_>, _>>();
+                Box::new(iter::once(array.map(Value::Array)))
+            }
+            Ast::Object(entries) => Self::object(entries, &env, json_data, Map::new()),
+            Ast::Pipe(lhs, rhs) => flat_map(Self::eval(lhs, &env, json_data), move |value| {
+                Self::eval(rhs, &env, value)
+            }),
+            Ast::Comma(lhs, rhs) => Box::new(
+                Self::eval(lhs, &env, json_data.clone()).chain(Self::eval(rhs, &env, json_data)),
+            ),
+            Ast::Binary(operator, lhs, rhs) => {
+                flat_map(Self::eval(rhs, &env, json_data.clone()), move |post| {
+                    Box::new(
+                        Self::eval(lhs, &env, json_data.clone())
+                            .map(move |pre| operator.apply(pre?, post.clone())),
+                    )
+                })
+            }
+            Ast::Negate(ast) => {
+                Box::new(Self::eval(ast, &env, json_data).map(|value| negate(value?)))
+            }
+            Ast::And(lhs, rhs) => flat_map(Self::eval(lhs, &env, json_data.clone()), move |pre| {
+                if !truthy(&pre) {
+                    return once(false.into());
+                }
+
+                Box::new(
+                    Self::eval(rhs, &env, json_data.clone()).map(|post| Ok(truthy(&post?).into())),
+                )
+            }),
+            Ast::Or(lhs, rhs) => flat_map(Self::eval(lhs, &env, json_data.clone()), move |pre| {
+                if truthy(&pre) {
+                    return once(true.into());
+                }
+
+                Box::new(
+                    Self::eval(rhs, &env, json_data.clone()).map(|post| Ok(truthy(&post?).into())),
+                )
+            }),
+            Ast::Alternative(lhs, rhs) => {
+                // Errors on the left are suppressed like falsy outputs.
+                let mut outputs = Self::eval(lhs, &env, json_data.clone())
+                    .filter_map(|value| value.ok().filter(truthy))
+                    .peekable();
+
+                if outputs.peek().is_none() {
+                    return Self::eval(rhs, &env, json_data);
+                }
+
+                Box::new(outputs.map(Ok))
+            }
+            Ast::If(condition, then, otherwise) => flat_map(
+                Self::eval(condition, &env, json_data.clone()),
+                move |condition| match truthy(&condition) {
+                    true => Self::eval(then, &env, json_data.clone()),
+                    false => Self::eval(otherwise, &env, json_data.clone()),
+                },
+            ),
+            // Variables of every alternative pattern are in scope, those the
+            // chosen pattern doesn't bind are null.
+            Ast::Bind(source, patterns, body) => {
+                let mut scope = env.clone();
+                if patterns.len() > 1 {
+                    for name in patterns.iter().flat_map(Pattern::variables) {
+                        scope = scope.bind(name, Value::Null);
+                    }
+                }
+
+                flat_map(Self::eval(source, &env, json_data.clone()), move |value| {
+                    let input = json_data.clone();
+                    let run =
+                        Rc::new(move |scope: &Env<'a>| Self::eval(body, scope, input.clone()));
+                    Self::bind(patterns, &scope, json_data.clone(), value, run)
+                })
+            }
+            // Only the first error is caught, the body isn't resumed after it.
+            Ast::Try(body, handler) => {
+                catch(Self::eval(body, &env, json_data), move |e| match handler {
+                    Some(handler) => Self::eval(handler, &env, e.into_value()),
+                    None => Box::new(iter::empty()),
+                })
+            }
+            Ast::Reduce(source, pattern, init, update) => {
+                flat_map(Self::eval(init, &env, json_data.clone()), move |init| {
+                    let reduced = Self::reduce(source, pattern, update, &env, &json_data, init);
+                    Box::new(iter::once(reduced))
+                })
+            }
+            Ast::Foreach(source, pattern, init, update, extract) => {
+                flat_map(Self::eval(init, &env, json_data.clone()), move |init| {
+                    let mut state = init;
+                    let (env, json_data) = (env.clone(), json_data.clone());
+                    flat_map(Self::eval(source, &env, json_data.clone()), move |value| {
+                        let scopes =
+                            match Self::destructure(pattern, env.clone(), &json_data, &value) {
+                                Ok(scopes) => scopes,
+                                Err(e) => return Box::new(iter::once(Err(e))),
+                            };
+
+                        // Every output of the update is a new state; the
+                        // outputs of one step are computed before any of
+                        // them is extracted.
+                        let mut outputs: Vec<Outputs<'a>> = vec![];
+                        for scope in scopes {
+                            for output in Self::eval(update, &scope, state.clone()) {
+                                let output = match output {
+                                    Ok(output) => output,
+                                    Err(e) => {
+                                        outputs.push(Box::new(iter::once(Err(e))));
+                                        return Box::new(outputs.into_iter().flatten());
+                                    }
+                                };
+
+                                state = output.clone();
+                                outputs.push(match extract {
+                                    Some(extract) => Self::eval(extract, &scope, output),
+                                    None => once(output),
+                                });
+                            }
+                        }
+
+                        Box::new(outputs.into_iter().flatten())
+                    })
+                })
+            }
+            Ast::Call(name, args) => match env.callable(name, args.len()).cloned() {
+                Some(Callable::Definition(definition, scope)) => flat_map(
+                    Self::call(definition, &scope, args, &env, json_data.clone()),
+                    move |scope| Self::eval(&definition.body, &scope, json_data.clone()),
+                ),
+                Some(Callable::Closure(ast, scope)) => Self::eval(ast, &scope, json_data),
+                None => match builtin::find(name, args.len()) {
+                    Some(function) => function(args, &env, json_data),
+                    None => Box::new(iter::once(Err(Error::Type(format!(
+                        "{}/{} is not defined",
+                        name,
+                        args.len()
+                    ))))),
+                },
+            },
+            Ast::Def(definition, rest) => {
+                let scope = env.define(
+                    &definition.name,
+                    Callable::Definition(definition, env.clone()),
+                );
+                Self::eval(rest, &scope, json_data)
+            }
+            Ast::Format(name) => {
+                let format = builtin::find_format(name).unwrap();
+                let string = format(builtin::integral(json_data));
+                Box::new(iter::once(string.map(Value::String)))
+            }
+            Ast::Interpolate(name, fragments) => {
+                let format = builtin::find_format(name).unwrap();
+                Self::interpolate(fragments, format, &env, json_data, String::new())
+            }
+            Ast::Assign(assignment, lhs, rhs) => {
+                Self::assign(assignment, lhs, rhs, &env, json_data)
+            }
+        }
+    }
+
+    /// Builds the strings of `fragments` followed by `suffix`, from the last
+    /// fragment to the first so that later interpolations vary slowest.
+    fn interpolate<'a>(
+        fragments: &'a [Fragment],
+        format: Format,
+        env: &Env<'a>,
+        json_data: Value,
+        suffix: String,
+    ) -> Outputs<'a> {
+        let (fragment, rest) = match fragments.split_last() {
+            Some(fragment) => fragment,
+            None => return once(Value::String(suffix)),
+        };
+
+        let ast = match fragment {
+            Fragment::Literal(string) => {
+                return Self::interpolate(rest, format, env, json_data, string.clone() + &suffix)
+            }
+            Fragment::Interpolation(ast) => ast,
+        };
+
+        let env = env.clone();
+        flat_map(
+            Self::eval(ast, &env, json_data.clone()),
+            move |value| match format(builtin::integral(value)) {
+                Ok(string) => {
+                    Self::interpolate(rest, format, &env, json_data.clone(), string + &suffix)
+                }
+                Err(e) => Box::new(iter::once(Err(e))),
+            },
+        )
+    }
+
+    /// Evaluates `ast` as a path expression. Every output is a value within
+    /// the root document together with its path; `json_data` lives at
+    /// `path`. Filters that don't select parts of their input, like `1` or
+    /// `. + 1`, are invalid path expressions.
+    pub fn paths<'a>(ast: &'a Ast, env: &Env<'a>, path: Path, json_data: Value) -> Paths<'a> {
+        let env = env.clone();
+        match ast {
+            Ast::Identity => Box::new(iter::once(Ok((path, json_data)))),
+            Ast::Field(target, key) => flat_map(
+                Self::paths(target, &env, path, json_data),
+                move |(path, value)| {
+                    let key = Value::String(key.clone());
+                    let value = Self::index(value, key.clone());
+                    Box::new(iter::once(
+                        value.map(|value| (path::append(&path, key), value)),
+                    ))
+                },
+            ),
+            Ast::Index(target, index) => {
+                flat_map(Self::eval(index, &env, json_data.clone()), move |index| {
+                    flat_map(
+                        Self::paths(target, &env, path.clone(), json_data.clone()),
+                        move |(path, value)| {
+                            let value = Self::index(value, index.clone());
+                            Box::new(iter::once(
+                                value.map(|value| (path::append(&path, index.clone()), value)),
+                            ))
+                        },
+                    )
+                })
+            }
+            Ast::Iterate(target) => flat_map(
+                Self::paths(target, &env, path, json_data),
+                |(path, value)| Self::iterate_paths(path, value),
+            ),
+            Ast::Pipe(lhs, rhs) => flat_map(
+                Self::paths(lhs, &env, path, json_data),
+                move |(path, value)| Self::paths(rhs, &env, path, value),
+            ),
+            Ast::Comma(lhs, rhs) => Box::new(
+                Self::paths(lhs, &env, path.clone(), json_data.clone())
+                    .chain(Self::paths(rhs, &env, path, json_data)),
+            ),
+            Ast::Alternative(lhs, rhs) => {
+                let mut paths = Self::paths(lhs, &env, path.clone(), json_data.clone())
+                    .filter_map(|output| output.ok().filter(|(_, value)| truthy(value)))
+                    .peekable();
+
+                if paths.peek().is_none() {
+                    return Self::paths(rhs, &env, path, json_data);
+                }
+
+                Box::new(paths.map(Ok))
+            }
+            Ast::If(condition, then, otherwise) => flat_map(
+                Self::eval(condition, &env, json_data.clone()),
+                move |condition| {
+                    let branch = if truthy(&condition) { then } else { otherwise };
+                    Self::paths(branch, &env, path.clone(), json_data.clone())
+                },
+            ),
+            Ast::Bind(source, patterns, body) => {
+                let mut scope = env.clone();
+                if patterns.len() > 1 {
+                    for name in patterns.iter().flat_map(Pattern::variables) {
+                        scope = scope.bind(name, Value::Null);
+                    }
+                }
+
+                flat_map(Self::eval(source, &env, json_data.clone()), move |value| {
+                    let (path, input) = (path.clone(), json_data.clone());
+                    let run = Rc::new(move |scope: &Env<'a>| {
+                        Self::paths(body, scope, path.clone(), input.clone())
+                    });
+                    Self::bind(patterns, &scope, json_data.clone(), value, run)
+                })
+            }
+            Ast::Try(body, handler) => {
+                catch(
+                    Self::paths(body, &env, path, json_data),
+                    move |e| match handler {
+                        Some(handler) => invalid_paths(Self::eval(handler, &env, e.into_value())),
+                        None => Box::new(iter::empty()),
+                    },
+                )
+            }
+            Ast::Call(name, args) => match env.callable(name, args.len()).cloned() {
+                Some(Callable::Definition(definition, scope)) => flat_map(
+                    Self::call(definition, &scope, args, &env, json_data.clone()),
+                    move |scope| {
+                        Self::paths(&definition.body, &scope, path.clone(), json_data.clone())
+                    },
+                ),
+                Some(Callable::Closure(ast, scope)) => Self::paths(ast, &scope, path, json_data),
+                None => match builtin::find_path(name, args.len()) {
+                    Some(function) => function(args, &env, path, json_data),
+                    None => invalid_paths(Self::eval(ast, &env, json_data)),
+                },
+            },
+            Ast::Def(definition, rest) => {
+                let scope = env.define(
+                    &definition.name,
+                    Callable::Definition(definition, env.clone()),
+                );
+                Self::paths(rest, &scope, path, json_data)
+            }
+            ast => invalid_paths(Self::eval(ast, &env, json_data)),
+        }
+    }
+
+    /// Paths of the values in an array or object.
+    pub fn iterate_paths<'a>(path: Path, value: Value) -> Paths<'a> {
+        let entries: Vec<(Value, Value)> = match value {
+            Value::Array(values) => (0..).map(Value::from).zip(values).collect(),
+            Value::Object(values) => values
+                .into_iter()
+                .map(|(key, value)| (Value::String(key), value))
+                .collect(),
+            value => {
+                return Box::new(iter::once(Err(Error::Type(format!(
+                    "Cannot iterate over {}",
+                    type_id(&value)
+                )))))
+            }
+        };
+
+        Box::new(
+            entries
+                .into_iter()
+                .map(move |(key, value)| Ok((path::append(&path, key), value))),
+        )
+    }
+
+    /// Updates every value `lhs` selects and emits the whole updated input.
+    /// `=` and the arithmetic forms emit one document for every output of
+    /// `rhs`, which sees the original input. `|=` replaces each value with the
+    /// first output of `rhs` run on it, deleting values it emits nothing for.
+    fn assign<'a>(
+        assignment: &'a Assignment,
+        lhs: &'a Ast,
+        rhs: &'a Ast,
+        env: &Env<'a>,
+        json_data: Value,
+    ) -> Outputs<'a> {
+        let paths = Self::paths(lhs, env, vec![], json_data.clone())
+            .map(|output| output.map(|(path, _)| path))
+            .collect::<Result<Vec<_>, _>>();
+        let paths = match paths {
+            Ok(paths) => paths,
+            Err(e) => return Box::new(iter::once(Err(e))),
+        };
+
+        if let Assignment::Update = assignment {
+            let mut document = json_data;
+            let mut deleted = vec![];
+            for path in paths {
+                let value = match path::get(&document, &path) {
+                    Ok(value) => value,
+                    Err(e) => return Box::new(iter::once(Err(e))),
+                };
+
+                match Self::eval(rhs, env, value).next() {
+                    Some(Ok(value)) => match path::set(document, &path, value) {
+                        Ok(updated) => document = updated,
+                        Err(e) => return Box::new(iter::once(Err(e))),
+                    },
+                    Some(Err(e)) => return Box::new(iter::once(Err(e))),
+                    None => deleted.push(path),
+                }
+            }
+
+            return Box::new(iter::once(path::delete(document, deleted)));
+        }
+
+        let env = env.clone();
+        flat_map(Self::eval(rhs, &env, json_data.clone()), move |value| {
+            let mut document = json_data.clone();
+            for path in &paths {
+                let updated = match assignment {
+                    Assignment::Arithmetic(operator) => path::get(&document, path)
+                        .and_then(|old| operator.apply(old, value.clone())),
+                    Assignment::Alternative => path::get(&document, path).map(|old| {
+                        if truthy(&old) {
+                            old
+                        } else {
+                            value.clone()
+                        }
+                    }),
+                    _ => Ok(value.clone()),
+                };
+
+                match updated.and_then(|updated| path::set(document, path, updated)) {
+                    Ok(updated) => document = updated,
+                    Err(e) => return Box::new(iter::once(Err(e))),
+                }
+            }
+
+            once(document)
+        })
+    }
+
+    /// Folds the outputs of `source` into `accumulator`. The last output of
+    /// the update becomes the new accumulator, null when there is none.
+    fn reduce<'a>(
+        source: &'a Ast,
+        pattern: &'a Pattern,
+        update: &'a Ast,
+        env: &Env<'a>,
+        json_data: &Value,
+        mut accumulator: Value,
+    ) -> Result<Value, Error> {
+        for value in Self::eval(source, env, json_data.clone()) {
+            for scope in Self::destructure(pattern, env.clone(), json_data, &value?)? {
+                let mut last = Value::Null;
+                for output in Self::eval(update, &scope, accumulator) {
+                    last = output?;
+                }
+                accumulator = last;
+            }
+        }
+
+        Ok(accumulator)
+    }
+
+    /// Scopes to run the body of a defined function in. The body sees the
+    /// scope it was defined in, the function itself for recursion and the
+    /// arguments of this call.
+    fn call<'a>(
+        definition: &'a Definition,
+        scope: &Env<'a>,
+        args: &'a [Ast],
+        caller: &Env<'a>,
+        json_data: Value,
+    ) -> Stream<'a, Env<'a>> {
+        let mut body = scope.define(
+            &definition.name,
+            Callable::Definition(definition, scope.clone()),
+        );
+        for (param, arg) in definition.params.iter().zip(args) {
+            body = body.define(param.name(), Callable::Closure(arg, caller.clone()));
+        }
+
+        Self::bind_params(definition, args, 0, body, caller.clone(), json_data)
+    }
+
+    /// Binds the value parameters from `index` on, once for every output of
+    /// their arguments.
+    fn bind_params<'a>(
+        definition: &'a Definition,
+        args: &'a [Ast],
+        index: usize,
+        scope: Env<'a>,
+        caller: Env<'a>,
+        json_data: Value,
+    ) -> Stream<'a, Env<'a>> {
+        let param = match definition.params.get(index) {
+            Some(Param::Value(name)) => name,
+            Some(Param::Filter(_)) => {
+                return Self::bind_params(definition, args, index + 1, scope, caller, json_data)
+            }
+            None => return Box::new(iter::once(Ok(scope))),
+        };
+
+        let arg = Self::eval(&args[index], &caller, json_data.clone());
+        flat_map(arg, move |value| {
+            let scope = scope.bind(param, value);
+            Self::bind_params(
+                definition,
+                args,
+                index + 1,
+                scope,
+                caller.clone(),
+                json_data.clone(),
+            )
+        })
+    }
+
+    /// Indexes arrays by number, counting negative indices from the end, and
+    /// objects by key. A `{"start", "end"}` object slices arrays and strings.
+    /// Indexing null yields null, indexing anything else is an error.
+    pub fn index(value: Value, index: Value) -> Result<Value, Error> {
+        let value = match (value, index) {
+            (Value::Array(values), Value::Number(index)) => {
+                let index = index.as_f64().unwrap_or_default().floor() as i64;
+                let index = if index < 0 {
+                    index + values.len() as i64
+                } else {
+                    index
+                };
+
+                usize::try_from(index)
+                    .ok()
+                    .and_then(|index| values.into_iter().nth(index))
+                    .unwrap_or_default()
+            }
+            (Value::Object(mut values), Value::String(key)) => {
+                values.remove(&key).unwrap_or_default()
+            }
+            (Value::Array(values), Value::Object(bounds)) => {
+                let range = path::slice(values.len(), &bounds)?;
+                Value::Array(values[range].to_vec())
+            }
+            (Value::String(string), Value::Object(bounds)) => {
+                let chars: Vec<char> = string.chars().collect();
+                let range = path::slice(chars.len(), &bounds)?;
+                Value::String(chars[range].iter().collect())
+            }
+            (Value::Null, Value::Number(_) | Value::String(_) | Value::Object(_)) => Value::Null,
+            (value, index) => {
+                return Err(Error::Type(format!(
+                    "Cannot index {} with {}",
+                    type_id(&value),
+                    type_id(&index)
+                )))
+            }
+        };
+
+        Ok(value)
+    }
+
+    pub fn iterate<'a>(value: Value) -> Outputs<'a> {
+        match value {
+            Value::Array(values) => Box::new(values.into_iter().map(Ok)),
+            Value::Object(values) => Box::new(values.into_iter().map(|(_, v)| Ok(v))),
+            value => Box::new(iter::once(Err(Error::Type(format!(
+                "Cannot iterate over {}",
+                type_id(&value)
+            ))))),
+        }
+    }
+
+    /// Builds one object for every combination of the entries' outputs.
+    fn object<'a>(
+        entries: &'a [(Ast, Ast)],
+        env: &Env<'a>,
+        json_data: Value,
+        object: Map<String, Value>,
+    ) -> Outputs<'a> {
+        let ((key, value), rest) = match entries.split_first() {
+            Some(entry) => entry,
+            None => return once(object.into()),
+        };
+
+        let env = env.clone();
+        flat_map(Self::eval(key, &env, json_data.clone()), move |key| {
+            let key = match key {
+                Value::String(key) => key,
+                key => {
+                    return Box::new(iter::once(Err(Error::Type(format!(
+                        "Object keys must be strings, not {}",
+                        type_id(&key)
+                    )))))
+                }
+            };
+
+            let env = env.clone();
+            let json_data = json_data.clone();
+            let object = object.clone();
+            flat_map(Self::eval(value, &env, json_data.clone()), move |value| {
+                let mut object = object.clone();
+                object.insert(key.clone(), value);
+                Self::object(rest, &env, json_data.clone(), object)
+            })
+        })
+    }
+
+    /// Runs the body of a binding with `value` destructured by the first of
+    /// `patterns` that fits. A pattern is abandoned for the next one as soon
+    /// as destructuring or the body fails, only the last pattern's errors are
+    /// reported.
+    fn bind<'a, T: 'a>(
+        patterns: &'a [Pattern],
+        env: &Env<'a>,
+        json_data: Value,
+        value: Value,
+        run: Body<'a, T>,
+    ) -> Stream<'a, T> {
+        let (pattern, rest) = match patterns.split_first() {
+            Some(pattern) => pattern,
+            None => return Box::new(iter::empty()),
+        };
+
+        let outputs: Stream<'a, T> =
+            match Self::destructure(pattern, env.clone(), &json_data, &value) {
+                Ok(scopes) => {
+                    let run = run.clone();
+                    Box::new(scopes.into_iter().flat_map(move |scope| run(&scope)))
+                }
+                Err(e) => Box::new(iter::once(Err(e))),
+            };
+
+        if rest.is_empty() {
+            return outputs;
+        }
+
+        let env = env.clone();
+        catch(outputs, move |_| {
+            Self::bind(rest, &env, json_data, value, run)
+        })
+    }
+
+    /// Binds the variables of `pattern` to the matching parts of `value`.
+    /// Computed object keys are evaluated against `json_data` and bind once
+    /// for every output.
+    fn destructure<'a>(
+        pattern: &'a Pattern,
+        env: Env<'a>,
+        json_data: &Value,
+        value: &Value,
+    ) -> Result<Vec<Env<'a>>, Error> {
+        let mut scopes = vec![env];
+        match pattern {
+            Pattern::Variable(name) => return Ok(vec![scopes[0].bind(name, value.clone())]),
+            Pattern::Array(patterns) => {
+                if !matches!(value, Value::Array(_) | Value::Null) {
+                    return Err(Error::Type(format!(
+                        "Cannot index {} with number",
+                        type_id(value)
+                    )));
+                }
+
+                for (i, pattern) in patterns.iter().enumerate() {
+                    let element = Self::index(value.clone(), i.into())?;
+                    let mut bound = vec![];
+                    for scope in scopes {
+                        bound.extend(Self::destructure(pattern, scope, json_data, &element)?);
+                    }
+                    scopes = bound;
+                }
+            }
+            Pattern::Object(entries) => {
+                for (key, patterns) in entries {
+                    let mut bound = vec![];
+                    for scope in scopes {
+                        for key in Self::eval(key, &scope, json_data.clone()) {
+                            let key = key?;
+                            if !key.is_string() || !matches!(value, Value::Object(_) | Value::Null)
+                            {
+                                return Err(Error::Type(format!(
+                                    "Cannot index {} with {}",
+                                    type_id(value),
+                                    type_id(&key)
+                                )));
+                            }
+
+                            let element = Self::index(value.clone(), key)?;
+                            let mut nested = vec![scope.clone()];
+                            for pattern in patterns {
+                                let mut next = vec![];
+                                for scope in nested {
+                                    next.extend(Self::destructure(
+                                        pattern, scope, json_data, &element,
+                                    )?);
+                                }
+                                nested = next;
+                            }
+                            bound.extend(nested);
+                        }
+                    }
+                    scopes = bound;
+                }
+            }
+        }
+
+        Ok(scopes)
+    }
+}
+
+/// Only `false` and `null` are falsy.
+pub fn truthy(value: &Value) -> bool {
+    !matches!(value, Value::Null | Value::Bool(false))
+}
+
+pub fn once<'a>(value: Value) -> Outputs<'a> {
+    Box::new(iter::once(Ok(value)))
+}
+
+/// Feeds every output of `outputs` into `f`, passing errors through.
+pub fn flat_map<'a, T: 'a, U: 'a>(
+    outputs: Stream<'a, T>,
+    mut f: impl FnMut(T) -> Stream<'a, U> + 'a,
+) -> Stream<'a, U> {
+    Box::new(outputs.flat_map(move |value| match value {
+        Ok(value) => f(value),
+        Err(e) => Box::new(iter::once(Err(e))),
+    }))
+}
+
+/// Passes `outputs` through up to their first error, then continues with the
+/// outputs `handler` produces for that error.
+pub fn catch<'a, T: 'a>(
+    mut outputs: Stream<'a, T>,
+    handler: impl FnOnce(Error) -> Stream<'a, T> + 'a,
+) -> Stream<'a, T> {
+    let mut handler = Some(handler);
+    Box::new(iter::from_fn(move || loop {
+        match outputs.next()? {
+            Ok(value) => return Some(Ok(value)),
+            Err(e) => match handler.take() {
+                Some(handler) => outputs = handler(e),
+                None => return Some(Err(e)),
+            },
+        }
+    }))
+}
+
+/// Rejects the outputs of a filter used where a path expression is expected.
+fn invalid_paths<'a>(outputs: Outputs<'a>) -> Paths<'a> {
+    Box::new(outputs.map(|value| {
+        Err(Error::Type(format!(
+            "Invalid path expression with result {}",
+            type_id(&value?)
+        )))
+    }))
+}
@@ -0,0 +1,201 @@
+use super::date;
+use super::operator::type_id;
+use crate::Error;
+use serde_json::Value;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn json(value: Value) -> Result<String, Error> {
+    Ok(value.to_string())
+}
+
+/// An array as a comma separated row, strings are quoted.
+pub fn csv(value: Value) -> Result<String, Error> {
+    row(value, "csv", ",", |value| match value {
+        Value::String(string) => Ok(format!("\"{}\"", string.replace('"', "\"\""))),
+        value => scalar(value, "is not valid in a csv row"),
+    })
+}
+
+/// An array as a tab separated row, tabs and line breaks in strings are
+/// escaped.
+pub fn tsv(value: Value) -> Result<String, Error> {
+    row(value, "tsv", "\t", |value| match value {
+        Value::String(string) => Ok(string
+            .replace('\\', "\\\\")
+            .replace('\t', "\\t")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r")),
+        value => scalar(value, "is not valid in a tsv row"),
+    })
+}
+
+/// Quotes strings for a POSIX shell, arrays become space separated words.
+pub fn sh(value: Value) -> Result<String, Error> {
+    let quote = |value| match value {
+        Value::String(string) => Ok(format!("'{}'", string.replace('\'', "'\\''"))),
+        value => scalar(value, "can not be escaped for shell"),
+    };
+
+    match value {
+        Value::Array(values) => Ok(values
+            .into_iter()
+            .map(quote)
+            .collect::<Result<Vec<_>, _>>()?
+            .join(" ")),
+        value => quote(value),
+    }
+}
+
+/// Percent-encodes every byte except the unreserved characters of RFC 3986.
+pub fn uri(value: Value) -> Result<String, Error> {
+    let mut uri = String::new();
+    for byte in text(value).bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(char::from(byte))
+            }
+            byte => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    Ok(uri)
+}
+
+pub fn html(value: Value) -> Result<String, Error> {
+    let mut html = String::new();
+    for char in text(value).chars() {
+        match char {
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '&' => html.push_str("&amp;"),
+            '\'' => html.push_str("&#39;"),
+            '"' => html.push_str("&quot;"),
+            char => html.push(char),
+        }
+    }
+
+    Ok(html)
+}
+
+pub fn base64(value: Value) -> Result<String, Error> {
+    let mut encoded = String::new();
+    for chunk in text(value).as_bytes().chunks(3) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, byte)| bits | u32::from(*byte) << (16 - 8 * i));
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(char::from(BASE64[(bits >> (18 - 6 * i) & 0x3f) as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    Ok(encoded)
+}
+
+/// Decodes base64 with or without padding. Bytes that aren't valid UTF-8
+/// are replaced.
+pub fn base64d(value: Value) -> Result<String, Error> {
+    let encoded = text(value);
+    let invalid = || {
+        let value = Value::from(encoded.as_str());
+        Error::Type(format!("{} is not valid base64 data", type_id(&value)))
+    };
+
+    let digits = encoded
+        .trim_end_matches('=')
+        .bytes()
+        .map(|byte| BASE64.iter().position(|digit| *digit == byte))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(invalid)?;
+    if digits.len() % 4 == 1 {
+        return Err(invalid());
+    }
+
+    let mut decoded = vec![];
+    for chunk in digits.chunks(4) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, digit)| bits | (*digit as u32) << (18 - 6 * i));
+
+        for i in 0..chunk.len() - 1 {
+            decoded.push((bits >> (16 - 8 * i)) as u8);
+        }
+    }
+
+    Ok(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+fn text(value: Value) -> String {
+    match value {
+        Value::String(string) => string,
+        value => match date::datetime(&value) {
+            Some(date) => String::from(date),
+            None => value.to_string(),
+        },
+    }
+}
+
+/// Formats every element of an array and joins them with `separator`.
+fn row(
+    value: Value,
+    format: &str,
+    separator: &str,
+    element: impl Fn(Value) -> Result<String, Error>,
+) -> Result<String, Error> {
+    match value {
+        Value::Array(values) => Ok(values
+            .into_iter()
+            .map(element)
+            .collect::<Result<Vec<_>, _>>()?
+            .join(separator)),
+        value => Err(Error::Type(format!(
+            "{} cannot be {}-formatted, only an array can be",
+            type_id(&value),
+            format
+        ))),
+    }
+}
+
+/// Formats the scalars that aren't strings as they are and null as nothing.
+fn scalar(value: Value, invalid: &str) -> Result<String, Error> {
+    match value {
+        Value::Null => Ok(String::new()),
+        Value::Bool(_) | Value::Number(_) => Ok(value.to_string()),
+        value => Err(Error::Type(format!("{} {}", type_id(&value), invalid))),
+    }
+}
+
+mod test_format {
+    #[test]
+    fn test_base64() {
+        use super::*;
+
+        let tests = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("héllo", "aMOpbGxv"),
+        ];
+
+        for (index, (decoded, encoded)) in tests.into_iter().enumerate() {
+            let result = base64(Value::from(decoded)).unwrap();
+            assert_eq!(result, encoded, "Failed testing index {}", index);
+
+            let result = base64d(Value::from(encoded)).unwrap();
+            assert_eq!(result, decoded, "Failed testing index {}", index);
+        }
+
+        assert_eq!(base64d(Value::from("Zm9vYg")).unwrap(), "foob");
+        assert!(base64d(Value::from("Zm9v!")).is_err());
+        assert!(base64d(Value::from("Zm9vY")).is_err());
+    }
+}
@@ -0,0 +1,546 @@
+use crate::error::{Error, Span};
+use serde_json::{Number, Value};
+use std::{fmt, str::FromStr};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    Dot,
+    DotDot,
+    Field(String),
+    Ident(String),
+    Variable(String),
+    /// `@name`, a format like `@text`.
+    Format(String),
+    Number(Number),
+    Str(String),
+    /// A string with `\(...)` interpolations.
+    Template(Vec<Segment>),
+    LeftBracket,
+    RightBracket,
+    LeftBrace,
+    RightBrace,
+    LeftParen,
+    RightParen,
+    Pipe,
+    Comma,
+    Colon,
+    Semicolon,
+    Question,
+    QuestionAlternative,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    SlashSlash,
+    Percent,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Assign,
+    PipeAssign,
+    PlusAssign,
+    MinusAssign,
+    StarAssign,
+    SlashAssign,
+    SlashSlashAssign,
+    PercentAssign,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Segment {
+    Literal(String),
+    Interpolation(Vec<(Token, Span)>),
+}
+
+pub struct Lexer<'a> {
+    filter: &'a str,
+    position: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(filter: &'a str) -> Self {
+        Self {
+            filter,
+            position: 0,
+        }
+    }
+
+    pub fn tokenize(mut self) -> Result<Vec<(Token, Span)>, Error> {
+        let mut tokens = vec![];
+        loop {
+            self.eat_while(char::is_whitespace);
+
+            let start = self.position;
+            match self.next_token()? {
+                Some(token) => tokens.push((token, start..self.position)),
+                None => return Ok(tokens),
+            }
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.filter[self.position..].chars().next()
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.filter[self.position..].chars().nth(n)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let char = self.peek()?;
+        self.position += char.len_utf8();
+        Some(char)
+    }
+
+    /// Consumes the second character of a two character token.
+    fn bump_into(&mut self, token: Token) -> Token {
+        self.bump();
+        token
+    }
+
+    fn eat_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let start = self.position;
+        while self.peek().is_some_and(&predicate) {
+            self.bump();
+        }
+
+        &self.filter[start..self.position]
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, Error> {
+        let char = match self.peek() {
+            Some(char) => char,
+            None => return Ok(None),
+        };
+        let token = match char {
+            '.' => {
+                self.bump();
+                if self.peek() == Some('.') {
+                    self.bump_into(Token::DotDot)
+                } else if self.peek().is_some_and(is_ident_start) {
+                    Token::Field(self.eat_while(is_ident_continue).to_string())
+                } else {
+                    Token::Dot
+                }
+            }
+            '$' if self.peek_nth(1).is_some_and(is_ident_start) => {
+                self.bump();
+                Token::Variable(self.eat_while(is_ident_continue).to_string())
+            }
+            '@' if self.peek_nth(1).is_some_and(is_ident_start) => {
+                self.bump();
+                Token::Format(self.eat_while(is_ident_continue).to_string())
+            }
+            '"' => self.string()?,
+            c if c.is_ascii_digit() => self.number()?,
+            c if is_ident_start(c) => Token::Ident(self.eat_while(is_ident_continue).to_string()),
+            _ => {
+                self.bump();
+                match char {
+                    '[' => Token::LeftBracket,
+                    ']' => Token::RightBracket,
+                    '{' => Token::LeftBrace,
+                    '}' => Token::RightBrace,
+                    '(' => Token::LeftParen,
+                    ')' => Token::RightParen,
+                    '|' if self.peek() == Some('=') => self.bump_into(Token::PipeAssign),
+                    '|' => Token::Pipe,
+                    ',' => Token::Comma,
+                    ':' => Token::Colon,
+                    ';' => Token::Semicolon,
+                    '?' if self.filter[self.position..].starts_with("//") => {
+                        self.bump();
+                        self.bump_into(Token::QuestionAlternative)
+                    }
+                    '?' => Token::Question,
+                    '+' if self.peek() == Some('=') => self.bump_into(Token::PlusAssign),
+                    '+' => Token::Plus,
+                    '-' if self.peek() == Some('=') => self.bump_into(Token::MinusAssign),
+                    '-' => Token::Minus,
+                    '*' if self.peek() == Some('=') => self.bump_into(Token::StarAssign),
+                    '*' => Token::Star,
+                    '/' if self.filter[self.position..].starts_with("/=") => {
+                        self.bump();
+                        self.bump_into(Token::SlashSlashAssign)
+                    }
+                    '/' if self.peek() == Some('/') => self.bump_into(Token::SlashSlash),
+                    '/' if self.peek() == Some('=') => self.bump_into(Token::SlashAssign),
+                    '/' => Token::Slash,
+                    '%' if self.peek() == Some('=') => self.bump_into(Token::PercentAssign),
+                    '%' => Token::Percent,
+                    '=' if self.peek() == Some('=') => self.bump_into(Token::Equal),
+                    '=' => Token::Assign,
+                    '!' if self.peek() == Some('=') => self.bump_into(Token::NotEqual),
+                    '<' if self.peek() == Some('=') => self.bump_into(Token::LessEqual),
+                    '>' if self.peek() == Some('=') => self.bump_into(Token::GreaterEqual),
+                    '<' => Token::Less,
+                    '>' => Token::Greater,
+                    _ => {
+                        return Err(Error::syntax(
+                            format!("unexpected character {:?}", char),
+                            self.position - char.len_utf8()..self.position,
+                        ))
+                    }
+                }
+            }
+        };
+
+        Ok(Some(token))
+    }
+
+    fn number(&mut self) -> Result<Token, Error> {
+        let start = self.position;
+        self.eat_while(|c| c.is_ascii_digit());
+
+        if self.peek() == Some('.') && self.peek_nth(1).is_some_and(|c| c.is_ascii_digit()) {
+            self.bump();
+            self.eat_while(|c| c.is_ascii_digit());
+        }
+
+        if matches!(self.peek(), Some('e' | 'E')) {
+            let sign = usize::from(matches!(self.peek_nth(1), Some('+' | '-')));
+            if self.peek_nth(1 + sign).is_some_and(|c| c.is_ascii_digit()) {
+                for _ in 0..=sign {
+                    self.bump();
+                }
+                self.eat_while(|c| c.is_ascii_digit());
+            }
+        }
+
+        let literal = &self.filter[start..self.position];
+        match Value::from_str(literal) {
+            Ok(Value::Number(number)) => Ok(Token::Number(number)),
+            _ => Err(Error::syntax(
+                format!("invalid number {}", literal),
+                start..self.position,
+            )),
+        }
+    }
+
+    /// Lexes a string literal. Strings containing `\(...)` interpolations
+    /// become a `Token::Template` holding the tokens of each interpolation.
+    fn string(&mut self) -> Result<Token, Error> {
+        let start = self.position;
+        self.bump();
+
+        let mut segments = vec![];
+        let mut literal = String::new();
+        let terminated = loop {
+            let escape = self.position;
+            match self.bump() {
+                Some('"') => break true,
+                Some('\\') => match self.bump() {
+                    Some('(') => {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                        segments.push(Segment::Interpolation(self.interpolation(escape)?));
+                    }
+                    Some('u') => literal.push(self.unicode_escape(escape)?),
+                    Some(char) => literal.push(match char {
+                        '"' => '"',
+                        '\\' => '\\',
+                        '/' => '/',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        _ => {
+                            return Err(Error::syntax(
+                                format!("invalid escape \\{}", char),
+                                escape..self.position,
+                            ))
+                        }
+                    }),
+                    None => break false,
+                },
+                Some(char) => literal.push(char),
+                None => break false,
+            }
+        };
+
+        if !terminated {
+            return Err(Error::syntax("unterminated string", start..self.position)
+                .with_hint("expected '\"'"));
+        }
+
+        if segments.is_empty() {
+            return Ok(Token::Str(literal));
+        }
+
+        segments.push(Segment::Literal(literal));
+        segments.retain(|segment| segment != &Segment::Literal(String::new()));
+        Ok(Token::Template(segments))
+    }
+
+    /// Lexes the tokens of a `\(...)` interpolation up to its closing
+    /// parenthesis.
+    fn interpolation(&mut self, start: usize) -> Result<Vec<(Token, Span)>, Error> {
+        let mut tokens = vec![];
+        let mut depth = 0;
+        loop {
+            self.eat_while(char::is_whitespace);
+
+            let token_start = self.position;
+            let token = match self.next_token()? {
+                Some(Token::RightParen) if depth == 0 => return Ok(tokens),
+                Some(token) => token,
+                None => {
+                    return Err(
+                        Error::syntax("unterminated interpolation", start..self.position)
+                            .with_hint("expected ')'"),
+                    )
+                }
+            };
+
+            match token {
+                Token::LeftParen => depth += 1,
+                Token::RightParen => depth -= 1,
+                _ => {}
+            }
+            tokens.push((token, token_start..self.position));
+        }
+    }
+
+    /// Decodes the hex digits of a `\uXXXX` escape, combining surrogate
+    /// pairs written as two escapes.
+    fn unicode_escape(&mut self, start: usize) -> Result<char, Error> {
+        let code = match self.hex() {
+            Some(high @ 0xD800..=0xDBFF) if self.filter[self.position..].starts_with("\\u") => {
+                self.position += 2;
+                match self.hex() {
+                    Some(low @ 0xDC00..=0xDFFF) => {
+                        Some(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+                    }
+                    _ => None,
+                }
+            }
+            code => code,
+        };
+
+        code.and_then(char::from_u32)
+            .ok_or_else(|| Error::syntax("invalid unicode escape", start..self.position))
+    }
+
+    fn hex(&mut self) -> Option<u32> {
+        let digits = self.filter.get(self.position..self.position + 4)?;
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let code = u32::from_str_radix(digits, 16).ok()?;
+        self.position += 4;
+        Some(code)
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let token = match self {
+            Token::Dot => ".",
+            Token::DotDot => "..",
+            Token::Field(key) => return write!(f, "'.{}'", key),
+            Token::Ident(ident) => return write!(f, "'{}'", ident),
+            Token::Variable(name) => return write!(f, "'${}'", name),
+            Token::Format(name) => return write!(f, "'@{}'", name),
+            Token::Number(number) => return write!(f, "'{}'", number),
+            Token::Str(string) => return write!(f, "{:?}", string),
+            Token::Template(_) => "string",
+            Token::LeftBracket => "[",
+            Token::RightBracket => "]",
+            Token::LeftBrace => "{",
+            Token::RightBrace => "}",
+            Token::LeftParen => "(",
+            Token::RightParen => ")",
+            Token::Pipe => "|",
+            Token::Comma => ",",
+            Token::Colon => ":",
+            Token::Semicolon => ";",
+            Token::Question => "?",
+            Token::QuestionAlternative => "?//",
+            Token::Plus => "+",
+            Token::Minus => "-",
+            Token::Star => "*",
+            Token::Slash => "/",
+            Token::SlashSlash => "//",
+            Token::Percent => "%",
+            Token::Equal => "==",
+            Token::NotEqual => "!=",
+            Token::Less => "<",
+            Token::LessEqual => "<=",
+            Token::Greater => ">",
+            Token::GreaterEqual => ">=",
+            Token::Assign => "=",
+            Token::PipeAssign => "|=",
+            Token::PlusAssign => "+=",
+            Token::MinusAssign => "-=",
+            Token::StarAssign => "*=",
+            Token::SlashAssign => "/=",
+            Token::SlashSlashAssign => "//=",
+            Token::PercentAssign => "%=",
+        };
+
+        write!(f, "'{}'", token)
+    }
+}
+
+fn is_ident_start(char: char) -> bool {
+    char.is_ascii_alphabetic() || char == '_'
+}
+
+fn is_ident_continue(char: char) -> bool {
+    char.is_ascii_alphanumeric() || char == '_'
+}
+
+mod test_lexer {
+    #[test]
+    fn tokenize_filters() {
+        use super::*;
+
+        struct TestLexer {
+            filter: &'static str,
+            tokens: Vec<Token>,
+        }
+
+        let tests = [
+            TestLexer {
+                filter: ".",
+                tokens: vec![Token::Dot],
+            },
+            TestLexer {
+                filter: " .a[0] | length",
+                tokens: vec![
+                    Token::Field("a".into()),
+                    Token::LeftBracket,
+                    Token::Number(0.into()),
+                    Token::RightBracket,
+                    Token::Pipe,
+                    Token::Ident("length".into()),
+                ],
+            },
+            TestLexer {
+                filter: r#"{"a.b": "c | \"d\""}"#,
+                tokens: vec![
+                    Token::LeftBrace,
+                    Token::Str("a.b".into()),
+                    Token::Colon,
+                    Token::Str(r#"c | "d""#.into()),
+                    Token::RightBrace,
+                ],
+            },
+            TestLexer {
+                filter: ".a<=1 != (.b>2)",
+                tokens: vec![
+                    Token::Field("a".into()),
+                    Token::LessEqual,
+                    Token::Number(1.into()),
+                    Token::NotEqual,
+                    Token::LeftParen,
+                    Token::Field("b".into()),
+                    Token::Greater,
+                    Token::Number(2.into()),
+                    Token::RightParen,
+                ],
+            },
+            TestLexer {
+                filter: "..|recurse(.a; . < 3)",
+                tokens: vec![
+                    Token::DotDot,
+                    Token::Pipe,
+                    Token::Ident("recurse".into()),
+                    Token::LeftParen,
+                    Token::Field("a".into()),
+                    Token::Semicolon,
+                    Token::Dot,
+                    Token::Less,
+                    Token::Number(3.into()),
+                    Token::RightParen,
+                ],
+            },
+            TestLexer {
+                filter: ".a // 1 / 2",
+                tokens: vec![
+                    Token::Field("a".into()),
+                    Token::SlashSlash,
+                    Token::Number(1.into()),
+                    Token::Slash,
+                    Token::Number(2.into()),
+                ],
+            },
+            TestLexer {
+                filter: ". as [$a] ?// $a | $a",
+                tokens: vec![
+                    Token::Dot,
+                    Token::Ident("as".into()),
+                    Token::LeftBracket,
+                    Token::Variable("a".into()),
+                    Token::RightBracket,
+                    Token::QuestionAlternative,
+                    Token::Variable("a".into()),
+                    Token::Pipe,
+                    Token::Variable("a".into()),
+                ],
+            },
+            TestLexer {
+                filter: r#""a\tb\u00e9\/" "x \(.a | "\(1)") y""#,
+                tokens: vec![
+                    Token::Str("a\tbé/".into()),
+                    Token::Template(vec![
+                        Segment::Literal("x ".into()),
+                        Segment::Interpolation(vec![
+                            (Token::Field("a".into()), 20..22),
+                            (Token::Pipe, 23..24),
+                            (
+                                Token::Template(vec![Segment::Interpolation(vec![(
+                                    Token::Number(1.into()),
+                                    28..29,
+                                )])]),
+                                25..31,
+                            ),
+                        ]),
+                        Segment::Literal(" y".into()),
+                    ]),
+                ],
+            },
+            TestLexer {
+                filter: "1.5e3 % 2",
+                tokens: vec![
+                    Token::Number(Number::from_f64(1500.0).unwrap()),
+                    Token::Percent,
+                    Token::Number(2.into()),
+                ],
+            },
+        ];
+
+        for (i, test) in tests.into_iter().enumerate() {
+            let tokens: Vec<_> = Lexer::new(test.filter)
+                .tokenize()
+                .unwrap()
+                .into_iter()
+                .map(|(token, _)| token)
+                .collect();
+            assert_eq!(tokens, test.tokens, "Failed testing index {}", i);
+        }
+    }
+
+    #[test]
+    fn track_spans() {
+        use super::*;
+
+        let spans: Vec<_> = Lexer::new(r#" .foo[12] | "a b""#)
+            .tokenize()
+            .unwrap()
+            .into_iter()
+            .map(|(_, span)| span)
+            .collect();
+        assert_eq!(spans, vec![1..5, 5..6, 6..8, 8..9, 10..11, 12..17]);
+
+        let error = Lexer::new(".a | !").tokenize().unwrap_err();
+        assert!(matches!(error, Error::Syntax { span, .. } if span == (5..6)));
+
+        let error = Lexer::new(r#".a + "b"#).tokenize().unwrap_err();
+        assert!(matches!(error, Error::Syntax { span, .. } if span == (5..7)));
+    }
+}
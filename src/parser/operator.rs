@@ -0,0 +1,513 @@
+use super::date;
+use crate::Error;
+use serde_json::{Map, Number, Value};
+use std::cmp::Ordering;
+
+/// Longest string, in bytes, that multiplying a string can produce.
+const MAX_REPEAT: usize = i32::MAX as usize;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Operator {
+    Addition,
+    Subtration,
+    Multiplication,
+    Division,
+    Modulo,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+impl Operator {
+    pub fn apply(&self, pre: Value, post: Value) -> Result<Value, Error> {
+        match *self {
+            Operator::Addition => Self::add_json_data(pre, post),
+            Operator::Subtration => Self::subtract_json_data(pre, post),
+            Operator::Multiplication => Self::multiply_json_data(pre, post),
+            Operator::Division => Self::divide_json_data(pre, post),
+            Operator::Modulo => Self::modulo_json_data(pre, post),
+            Operator::Equal => Ok((compare(&pre, &post) == Ordering::Equal).into()),
+            Operator::NotEqual => Ok((compare(&pre, &post) != Ordering::Equal).into()),
+            Operator::Less => Ok((compare(&pre, &post) == Ordering::Less).into()),
+            Operator::LessEqual => Ok((compare(&pre, &post) != Ordering::Greater).into()),
+            Operator::Greater => Ok((compare(&pre, &post) == Ordering::Greater).into()),
+            Operator::GreaterEqual => Ok((compare(&pre, &post) != Ordering::Less).into()),
+        }
+    }
+
+    fn add_json_data(pre: Value, post: Value) -> Result<Value, Error> {
+        let pre_type_id = type_id(&pre);
+        let post_type_id = type_id(&post);
+        let value = match (pre, post) {
+            (Value::Array(e), Value::Array(f)) => [e, f].concat().into(),
+            (Value::Object(mut e), Value::Object(mut f)) => {
+                e.append(&mut f);
+                e.into()
+            }
+            (Value::Number(a), Value::Number(e)) => {
+                number(Self::convert_to_f64(a) + Self::convert_to_f64(e))
+            }
+            (Value::String(a), Value::String(e)) => [a, e].concat().into(),
+            (e, Value::Null) | (Value::Null, e) => e,
+            _ => {
+                return Err(Error::Type(format!(
+                    "{} and {} cannot be added",
+                    pre_type_id, post_type_id
+                )))
+            }
+        };
+
+        Ok(value)
+    }
+
+    fn subtract_json_data(pre: Value, post: Value) -> Result<Value, Error> {
+        let pre_type_id = type_id(&pre);
+        let post_type_id = type_id(&post);
+        let value = match (pre, post) {
+            (Value::Array(e), Value::Array(f)) => {
+                let mut result = vec![];
+
+                for value in e {
+                    if !f.contains(&value) {
+                        result.push(value)
+                    }
+                }
+
+                result.into()
+            }
+            (Value::Number(a), Value::Number(e)) => {
+                number(Self::convert_to_f64(a) - Self::convert_to_f64(e))
+            }
+            _ => {
+                return Err(Error::Type(format!(
+                    "{} and {} cannot be subtracted",
+                    pre_type_id, post_type_id
+                )))
+            }
+        };
+
+        Ok(value)
+    }
+
+    fn multiply_json_data(pre: Value, post: Value) -> Result<Value, Error> {
+        let pre_type_id = type_id(&pre);
+        let post_type_id = type_id(&post);
+        let value = match (pre, post) {
+            (Value::Object(a), Value::Object(mut e)) => {
+                let mut result = Map::new();
+
+                for (key, pre_value) in a {
+                    if let Some(post_value) = e.get(key.as_str()) {
+                        result.insert(
+                            key.clone(),
+                            Self::multiply_json_data(pre_value, post_value.clone())?,
+                        );
+                        e.remove(&key);
+                    } else {
+                        result.insert(key, pre_value);
+                    }
+                }
+
+                result.extend(e);
+                result.into()
+            }
+            (Value::Number(a), Value::Number(e)) => {
+                number(Self::convert_to_f64(a) * Self::convert_to_f64(e))
+            }
+            (Value::String(e), Value::Number(a)) | (Value::Number(a), Value::String(e)) => {
+                let a = Self::convert_to_f64(a);
+                if a < 1.0 {
+                    return Ok(Value::Null);
+                }
+
+                let length = e.len().checked_mul(a as usize);
+                match length.filter(|length| *length <= MAX_REPEAT) {
+                    Some(_) => e.repeat(a as usize).into(),
+                    None => {
+                        return Err(Error::Type(format!(
+                            "{} and {} repeat into a string too long",
+                            pre_type_id, post_type_id
+                        )))
+                    }
+                }
+            }
+            _ => {
+                return Err(Error::Type(format!(
+                    "{} and {} cannot be multiplied",
+                    pre_type_id, post_type_id
+                )))
+            }
+        };
+
+        Ok(value)
+    }
+
+    fn divide_json_data(pre: Value, post: Value) -> Result<Value, Error> {
+        let pre_type_id = type_id(&pre);
+        let post_type_id = type_id(&post);
+        let error = || {
+            Error::Type(format!(
+                "{} and {} cannot be divided",
+                pre_type_id, post_type_id
+            ))
+        };
+
+        let value = match (pre, post) {
+            (Value::String(e), Value::String(a)) => {
+                let value: Vec<_> = e.split(&a).collect();
+                value.into()
+            }
+            (Value::Number(e), Value::Array(a)) => {
+                let mut result = vec![];
+                let e = Self::convert_to_f64(e);
+
+                for value in a {
+                    match value {
+                        Value::Number(a) => {
+                            let a = Self::convert_to_f64(a);
+
+                            if a == 0.0 {
+                                return Err(error());
+                            }
+
+                            result.push(number(e / a))
+                        }
+                        _ => return Err(error()),
+                    }
+                }
+
+                result.into()
+            }
+            (Value::Array(e), Value::Number(a)) => {
+                let mut result = vec![];
+                let a = Self::convert_to_f64(a);
+                if a == 0.0 {
+                    return Err(error());
+                }
+
+                for value in e {
+                    match value {
+                        Value::Number(e) => {
+                            let e = Self::convert_to_f64(e);
+
+                            result.push(number(e / a))
+                        }
+                        _ => return Err(error()),
+                    }
+                }
+
+                result.into()
+            }
+            (Value::Number(e), Value::Number(a)) => {
+                let e = Self::convert_to_f64(e);
+                let a = Self::convert_to_f64(a);
+
+                if a == 0.0 {
+                    return Err(error());
+                }
+
+                number(e / a)
+            }
+            _ => return Err(error()),
+        };
+
+        Ok(value)
+    }
+
+    fn convert_to_f64(value: Number) -> f64 {
+        if value.is_f64() {
+            value.as_f64().unwrap_or_default()
+        } else if let Some(value) = value.as_i64() {
+            value as f64
+        } else if let Some(value) = value.as_u64() {
+            value as f64
+        } else {
+            0.0
+        }
+    }
+
+    fn modulo_json_data(pre: Value, post: Value) -> Result<Value, Error> {
+        let pre_type_id = type_id(&pre);
+        let post_type_id = type_id(&post);
+        let error = || {
+            Error::Type(format!(
+                "{} and {} cannot be divided because the divisor is zero",
+                pre_type_id, post_type_id
+            ))
+        };
+
+        let value = match (pre, post) {
+            (Value::Number(e), Value::Array(a)) => {
+                let mut result = vec![];
+                let e = Self::convert_to_f64(e);
+
+                for value in a {
+                    match value {
+                        Value::Number(a) => {
+                            let a = Self::convert_to_f64(a);
+
+                            if a == 0.0 {
+                                return Err(error());
+                            }
+
+                            result.push(number(e % a))
+                        }
+                        _ => return Err(modulo_type_error(&pre_type_id, &post_type_id)),
+                    }
+                }
+
+                result.into()
+            }
+            (Value::Array(e), Value::Number(a)) => {
+                let mut result = vec![];
+                let a = Self::convert_to_f64(a);
+                if a == 0.0 {
+                    return Err(error());
+                }
+
+                for value in e {
+                    match value {
+                        Value::Number(e) => {
+                            let e = Self::convert_to_f64(e);
+
+                            result.push(number(e % a))
+                        }
+                        _ => return Err(modulo_type_error(&pre_type_id, &post_type_id)),
+                    }
+                }
+
+                result.into()
+            }
+            (Value::Number(e), Value::Number(a)) => {
+                let e = Self::convert_to_f64(e);
+                let a = Self::convert_to_f64(a);
+
+                if a == 0.0 {
+                    return Err(error());
+                }
+
+                number(e % a)
+            }
+            _ => return Err(modulo_type_error(&pre_type_id, &post_type_id)),
+        };
+
+        Ok(value)
+    }
+}
+
+pub fn negate(value: Value) -> Result<Value, Error> {
+    match value {
+        Value::Number(number) => Ok(number
+            .as_i64()
+            .and_then(i64::checked_neg)
+            .map(Value::from)
+            .unwrap_or_else(|| Value::from(-Operator::convert_to_f64(number)))),
+        value => Err(Error::Type(format!(
+            "{} cannot be negated",
+            type_id(&value)
+        ))),
+    }
+}
+
+/// Orders values the way jq does: `null < false < true < numbers < strings
+/// < arrays < objects`. Arrays compare element by element, objects first by
+/// their sorted keys and then by the values under those keys. TOML
+/// datetimes compare by the instant they stand for.
+pub fn compare(pre: &Value, post: &Value) -> Ordering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Null => 0,
+            Value::Bool(false) => 1,
+            Value::Bool(true) => 2,
+            Value::Number(_) => 3,
+            Value::String(_) => 4,
+            Value::Array(_) => 5,
+            Value::Object(_) => 6,
+        }
+    }
+
+    if let (Some(a), Some(e)) = (date::datetime(pre), date::datetime(post)) {
+        let instant = |date| date::parse_iso8601(date).ok();
+        return instant(a)
+            .partial_cmp(&instant(e))
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| a.cmp(e));
+    }
+
+    match (pre, post) {
+        (Value::Number(a), Value::Number(e)) => {
+            let a = Operator::convert_to_f64(a.clone());
+            let e = Operator::convert_to_f64(e.clone());
+            a.partial_cmp(&e).unwrap_or(Ordering::Equal)
+        }
+        (Value::String(a), Value::String(e)) => a.cmp(e),
+        (Value::Array(a), Value::Array(e)) => a
+            .iter()
+            .zip(e)
+            .map(|(a, e)| compare(a, e))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&e.len())),
+        (Value::Object(a), Value::Object(e)) => {
+            let mut a_keys: Vec<_> = a.keys().collect();
+            let mut e_keys: Vec<_> = e.keys().collect();
+            a_keys.sort();
+            e_keys.sort();
+
+            a_keys.cmp(&e_keys).then_with(|| {
+                a_keys
+                    .iter()
+                    .map(|key| compare(&a[key.as_str()], &e[key.as_str()]))
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            })
+        }
+        _ => rank(pre).cmp(&rank(post)),
+    }
+}
+
+fn modulo_type_error(pre_type_id: &str, post_type_id: &str) -> Error {
+    Error::Type(format!(
+        "{} and {} cannot be divided",
+        pre_type_id, post_type_id
+    ))
+}
+
+/// Integral numbers are emitted as integers. JSON has no infinities or NaN,
+/// so infinities become the largest finite numbers and NaN becomes null.
+pub fn number(n: f64) -> Value {
+    if n.is_infinite() {
+        return Value::from(f64::MAX.copysign(n));
+    }
+
+    if n.fract() == 0.0 && n.abs() < i64::MAX as f64 {
+        return Value::from(n as i64);
+    }
+
+    Value::from(n)
+}
+
+/// Name of the value's type, as reported by `type`.
+pub fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Describes a value the way error messages refer to it, e.g. `number (1)`.
+pub fn type_id(value: &Value) -> String {
+    let type_name = type_name(value);
+    let mut value = value.to_string();
+    if value.chars().count() > 11 {
+        value = value.chars().take(10).collect::<String>() + "...";
+    }
+
+    format!("{} ({})", type_name, value)
+}
+
+mod test_operator {
+    #[test]
+    fn mismatched_types() {
+        use super::*;
+        use serde_json::json;
+
+        let tests = [
+            (Operator::Addition, json!(1), json!("a")),
+            (Operator::Subtration, json!("a"), json!("b")),
+            (Operator::Multiplication, json!([]), json!({})),
+            (Operator::Division, json!(1), json!(0)),
+            (Operator::Modulo, json!(true), json!(2)),
+        ];
+
+        for (i, (operator, pre, post)) in tests.into_iter().enumerate() {
+            let result = operator.apply(pre, post);
+            assert!(
+                matches!(result, Err(Error::Type(_))),
+                "Failed testing index {}",
+                i
+            );
+        }
+    }
+
+    #[test]
+    fn repeat_strings() {
+        use super::*;
+        use serde_json::json;
+
+        let result = Operator::Multiplication
+            .apply(json!("ab"), json!(3))
+            .unwrap();
+        assert_eq!(result, json!("ababab"));
+
+        let result = Operator::Multiplication.apply(json!("a"), json!(1e18));
+        assert!(matches!(result, Err(Error::Type(_))));
+    }
+
+    #[test]
+    fn describe_mismatched_types() {
+        use super::*;
+        use serde_json::json;
+
+        let error = Operator::Addition
+            .apply(json!({"name": "ruq", "version": 1}), json!(1))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"object ({"name":"r...) and number (1) cannot be added"#
+        );
+    }
+
+    #[test]
+    fn order_across_types() {
+        use super::*;
+        use serde_json::json;
+
+        let ordered = [
+            json!(null),
+            json!(false),
+            json!(true),
+            json!(-1),
+            json!(1),
+            json!(1.5),
+            json!(""),
+            json!("a"),
+            json!("b"),
+            json!([]),
+            json!([1]),
+            json!([1, 0]),
+            json!([2]),
+            json!({}),
+            json!({"a": 2}),
+            json!({"a": 1, "b": 0}),
+            json!({"b": 0}),
+        ];
+
+        for (i, pair) in ordered.windows(2).enumerate() {
+            assert_eq!(
+                compare(&pair[0], &pair[1]),
+                Ordering::Less,
+                "Failed testing index {}",
+                i
+            );
+            assert_eq!(
+                compare(&pair[1], &pair[0]),
+                Ordering::Greater,
+                "Failed testing index {}",
+                i
+            );
+        }
+
+        assert_eq!(compare(&json!(1), &json!(1.0)), Ordering::Equal);
+        assert_eq!(
+            compare(&json!({"a": [1, {"b": 2}]}), &json!({"a": [1.0, {"b": 2}]})),
+            Ordering::Equal
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use super::evaluator::{Evaluator, Stream};
+use super::operator::{compare, type_id};
+use crate::Error;
+use serde_json::{Map, Value};
+use std::ops::Range;
+
+/// Largest array index assignments can pad an array up to, the same as
+/// jq's.
+const MAX_INDEX: usize = (i32::MAX >> 2) as usize;
+
+/// Keys and array indices leading from the root of a document to a value.
+pub type Path = Vec<Value>;
+
+/// Outputs of a path expression, each a path together with the value there.
+pub type Paths<'a> = Stream<'a, (Path, Value)>;
+
+pub fn append(path: &Path, key: Value) -> Path {
+    let mut path = path.clone();
+    path.push(key);
+    path
+}
+
+/// The value at `path`, null where it doesn't exist.
+pub fn get(value: &Value, path: &[Value]) -> Result<Value, Error> {
+    path.iter().try_fold(value.clone(), |value, key| {
+        Evaluator::index(value, key.clone())
+    })
+}
+
+/// Replaces the value at `path` with `new`. Missing objects and arrays on the
+/// way are created and arrays are padded with nulls.
+pub fn set(value: Value, path: &[Value], new: Value) -> Result<Value, Error> {
+    let (key, rest) = match path.split_first() {
+        Some(key) => key,
+        None => return Ok(new),
+    };
+
+    match (value, key) {
+        (Value::Object(mut values), Value::String(key)) => {
+            let nested = values.remove(key).unwrap_or_default();
+            values.insert(key.clone(), set(nested, rest, new)?);
+            Ok(Value::Object(values))
+        }
+        (Value::Array(mut values), Value::Number(_)) => {
+            let index = array_index(&values, key)?;
+            if index > MAX_INDEX {
+                return Err(Error::Type(String::from("Out of bounds array index")));
+            }
+
+            if index >= values.len() {
+                values.resize(index + 1, Value::Null);
+            }
+
+            values[index] = set(values[index].take(), rest, new)?;
+            Ok(Value::Array(values))
+        }
+        (Value::Array(mut values), Value::Object(bounds)) => {
+            let range = slice(values.len(), bounds)?;
+            let current = Value::Array(values[range.clone()].to_vec());
+            match set(current, rest, new)? {
+                Value::Array(new) => {
+                    values.splice(range, new);
+                    Ok(Value::Array(values))
+                }
+                _ => Err(Error::Type(String::from(
+                    "A slice of an array can only be assigned another array",
+                ))),
+            }
+        }
+        (Value::Null, Value::String(_)) => set(Value::Object(Map::new()), path, new),
+        (Value::Null, Value::Number(_) | Value::Object(_)) => set(Value::Array(vec![]), path, new),
+        (value, key) => Err(Error::Type(format!(
+            "Cannot index {} with {}",
+            type_id(&value),
+            type_id(key)
+        ))),
+    }
+}
+
+/// Removes the values at `paths`. Later array elements go first so that
+/// removing one doesn't shift the indices of the others.
+pub fn delete(mut value: Value, mut paths: Vec<Path>) -> Result<Value, Error> {
+    paths.sort_by(|a, b| compare(&Value::from(b.clone()), &Value::from(a.clone())));
+    paths.dedup();
+
+    for path in paths {
+        value = delete_path(value, &path)?;
+    }
+
+    Ok(value)
+}
+
+fn delete_path(value: Value, path: &[Value]) -> Result<Value, Error> {
+    let (key, rest) = match path.split_first() {
+        Some(key) => key,
+        None => return Ok(Value::Null),
+    };
+
+    match (value, key) {
+        (Value::Object(mut values), Value::String(key)) => {
+            if let Some(nested) = values.remove(key).filter(|_| !rest.is_empty()) {
+                values.insert(key.clone(), delete_path(nested, rest)?);
+            }
+            Ok(Value::Object(values))
+        }
+        (Value::Array(mut values), Value::Number(_)) => {
+            let index = array_index(&values, key)?;
+            if index < values.len() && rest.is_empty() {
+                values.remove(index);
+            } else if index < values.len() {
+                values[index] = delete_path(values[index].take(), rest)?;
+            }
+            Ok(Value::Array(values))
+        }
+        (Value::Array(mut values), Value::Object(bounds)) => {
+            let range = slice(values.len(), bounds)?;
+            if rest.is_empty() {
+                values.drain(range);
+            } else if let Value::Array(nested) =
+                delete_path(Value::Array(values[range.clone()].to_vec()), rest)?
+            {
+                values.splice(range, nested);
+            }
+            Ok(Value::Array(values))
+        }
+        (Value::Null, _) => Ok(Value::Null),
+        (value, key) => Err(Error::Type(format!(
+            "Cannot delete field at {} of {}",
+            type_id(key),
+            type_id(&value)
+        ))),
+    }
+}
+
+/// Resolves the `{"start", "end"}` bounds of a slice of `len` elements.
+/// Negative bounds count from the end, missing ones default to the whole
+/// sequence and bounds out of range are clamped.
+pub fn slice(len: usize, bounds: &Map<String, Value>) -> Result<Range<usize>, Error> {
+    let bound = |key: &str, default: f64, round: fn(f64) -> f64| {
+        let bound = match bounds.get(key) {
+            Some(Value::Number(bound)) => round(bound.as_f64().unwrap_or_default()),
+            None | Some(Value::Null) => default,
+            Some(_) => {
+                return Err(Error::Type(String::from(
+                    "Start and end indices of a slice must be numbers",
+                )))
+            }
+        };
+
+        let bound = if bound < 0.0 {
+            bound + len as f64
+        } else {
+            bound
+        };
+        Ok(bound.clamp(0.0, len as f64) as usize)
+    };
+
+    let start = bound("start", 0.0, f64::floor)?;
+    let end = bound("end", len as f64, f64::ceil)?;
+    Ok(start..end.max(start))
+}
+
+/// Resolves an index into `values`, counting negative ones from the end.
+fn array_index(values: &[Value], index: &Value) -> Result<usize, Error> {
+    let index = index.as_f64().unwrap_or_default().floor() as i64;
+    let index = if index < 0 {
+        index + values.len() as i64
+    } else {
+        index
+    };
+
+    match usize::try_from(index) {
+        Ok(index) => Ok(index),
+        Err(_) => Err(Error::Type(String::from(
+            "Out of bounds negative array index",
+        ))),
+    }
+}
+
+mod test_path {
+    #[test]
+    fn test_set() {
+        use super::*;
+        use serde_json::json;
+
+        struct TestSet {
+            value: Value,
+            path: Value,
+            result: Value,
+        }
+
+        let tests = [
+            TestSet {
+                value: json!({"a": {"b": 1}}),
+                path: json!(["a", "b"]),
+                result: json!({"a": {"b": 2}}),
+            },
+            TestSet {
+                value: json!(null),
+                path: json!(["a", 1]),
+                result: json!({"a": [null, 2]}),
+            },
+            TestSet {
+                value: json!([1, 2, 3]),
+                path: json!([-1]),
+                result: json!([1, 2, 2]),
+            },
+            TestSet {
+                value: json!(1),
+                path: json!([]),
+                result: json!(2),
+            },
+        ];
+
+        for (index, test) in tests.into_iter().enumerate() {
+            let path: Path = serde_json::from_value(test.path).unwrap();
+            let result = set(test.value, &path, json!(2)).unwrap();
+            assert_eq!(result, test.result, "Failed testing index {}", index);
+        }
+
+        assert!(set(json!({"a": 1}), &[json!(0)], json!(2)).is_err());
+        assert!(set(json!([1]), &[json!(-2)], json!(2)).is_err());
+
+        let error = set(json!([]), &[json!(1e12)], json!(2)).unwrap_err();
+        assert_eq!(error.to_string(), "Out of bounds array index");
+    }
+
+    #[test]
+    fn test_delete() {
+        use super::*;
+        use serde_json::json;
+
+        let value = json!({"a": [1, 2, 3, 4], "b": {"c": 1, "d": 2}});
+        let paths = vec![
+            vec![json!("a"), json!(0)],
+            vec![json!("a"), json!(2)],
+            vec![json!("a"), json!(0)],
+            vec![json!("b"), json!("c")],
+            vec![json!("x"), json!("y")],
+        ];
+
+        let result = delete(value, paths).unwrap();
+        assert_eq!(result, json!({"a": [2, 4], "b": {"d": 2}}));
+    }
+}
@@ -1,6 +1,8 @@
-pub trait Processor {
-    type T;
-    fn from_json(json_data: serde_json::Value) -> Self::T;
-    fn to_json(&self) -> serde_json::Value;
-    fn to_string(&self) -> String;
-}
+use crate::Error;
+
+pub trait Processor {
+    type T;
+    fn from_json(json_data: serde_json::Value) -> Result<Self::T, Error>;
+    fn to_json(&self) -> Result<serde_json::Value, Error>;
+    fn to_string(&self) -> Result<String, Error>;
+}
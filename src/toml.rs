@@ -1,4 +1,11 @@
 use super::processor::Processor;
+use crate::Error;
+use serde_json::Value;
+
+/// Key serde wraps TOML datetimes in. Filters keep the wrapper, so a
+/// datetime stays one wherever it's moved, and the date builtins and
+/// comparisons read the datetime string inside it.
+pub const DATETIME: &str = "$__toml_private_datetime";
 
 #[derive(Debug, Clone)]
 pub struct Toml {
@@ -6,9 +13,12 @@ pub struct Toml {
 }
 
 impl Toml {
-    pub fn new(data: String) -> Self {
-        let data = toml::from_str(&data).unwrap();
-        Toml { data }
+    pub fn new(data: String) -> Result<Self, Error> {
+        let data = toml::from_str(&data).map_err(|e| {
+            let position = e.line_col().map(|(line, column)| (line + 1, column + 1));
+            Error::decode("TOML", &e, &data, position)
+        })?;
+        Ok(Toml { data })
     }
 
     #[allow(dead_code)]
@@ -20,17 +30,42 @@ impl Toml {
 impl Processor for Toml {
     type T = Toml;
 
-    fn from_json(json_data: serde_json::Value) -> Self::T {
-        let data: toml::Value = serde_json::from_value(json_data).unwrap();
-        Toml { data }
+    fn from_json(json_data: serde_json::Value) -> Result<Self::T, Error> {
+        let data: toml::Value = serde_json::from_value(json_data).map_err(encode_error)?;
+        Ok(Toml { data })
+    }
+
+    fn to_json(&self) -> Result<serde_json::Value, Error> {
+        serde_json::to_value(self.data.clone()).map_err(|e| Error::decode("TOML", e, "", None))
+    }
+
+    fn to_string(&self) -> Result<String, Error> {
+        toml::to_string_pretty(&self.data).map_err(encode_error)
     }
+}
 
-    fn to_json(&self) -> serde_json::Value {
-        serde_json::to_value(self.data.clone()).unwrap()
+/// Replaces wrapped datetimes with their strings, for languages without
+/// datetimes.
+pub fn unwrap_datetimes(json_data: Value) -> Value {
+    match json_data {
+        Value::Object(mut values) if values.len() == 1 && values.contains_key(DATETIME) => {
+            values.remove(DATETIME).unwrap_or_default()
+        }
+        Value::Array(values) => values.into_iter().map(unwrap_datetimes).collect(),
+        Value::Object(values) => Value::Object(
+            values
+                .into_iter()
+                .map(|(key, value)| (key, unwrap_datetimes(value)))
+                .collect(),
+        ),
+        value => value,
     }
+}
 
-    fn to_string(&self) -> String {
-        toml::to_string_pretty(&self.data).unwrap()
+fn encode_error(e: impl std::fmt::Display) -> Error {
+    Error::Encode {
+        language: String::from("TOML"),
+        message: e.to_string(),
     }
 }
 
@@ -47,7 +82,7 @@ fn convert_json_to_toml() {
         }"#;
     let json_marshalled_val = serde_json::from_str(json_data).unwrap();
 
-    let toml = Toml::from_json(json_marshalled_val).get_toml();
+    let toml = Toml::from_json(json_marshalled_val).unwrap().get_toml();
 
     let toml_val: toml::Value = toml::from_str(
         r#"
@@ -67,8 +102,8 @@ name = "John Doe"
 phones = ["+44 1234567", "+44 2345678"]
 "#;
 
-    let toml = Toml::new(toml_str.to_string());
-    let json_data = toml.to_json();
+    let toml = Toml::new(toml_str.to_string()).unwrap();
+    let json_data = toml.to_json().unwrap();
 
     let json_val: serde_json::Value = serde_json::from_str(
         r#"
@@ -117,7 +152,7 @@ fn convert_cargo_json_to_toml() {
 
     let json_marshalled_val = serde_json::from_str(json_data).unwrap();
 
-    let toml = Toml::from_json(json_marshalled_val).get_toml();
+    let toml = Toml::from_json(json_marshalled_val).unwrap().get_toml();
 
     let toml_val: toml::Value = toml::from_str(
         r#"
@@ -138,3 +173,58 @@ fn convert_cargo_json_to_toml() {
     .unwrap();
     assert_eq!(toml_val, toml);
 }
+
+#[test]
+fn convert_toml_datetimes() {
+    let toml_str = r#"created = 1979-05-27T07:32:00-08:00
+released = 1979-05-27
+tags = ["07:32:00", "v1"]
+"#;
+
+    let json_data = Toml::new(toml_str.to_string()).unwrap().to_json().unwrap();
+    assert_eq!(
+        json_data,
+        serde_json::json!({
+            "created": {DATETIME: "1979-05-27T07:32:00-08:00"},
+            "released": {DATETIME: "1979-05-27"},
+            "tags": ["07:32:00", "v1"]
+        })
+    );
+    assert_eq!(
+        unwrap_datetimes(json_data.clone()),
+        serde_json::json!({
+            "created": "1979-05-27T07:32:00-08:00",
+            "released": "1979-05-27",
+            "tags": ["07:32:00", "v1"]
+        })
+    );
+
+    let toml = Toml::from_json(json_data).unwrap().get_toml();
+    assert_eq!(toml, toml::from_str(toml_str).unwrap());
+
+    let json_data = serde_json::json!({"moved": {DATETIME: "1979-05-27"}});
+    let toml = Toml::from_json(json_data).unwrap().get_toml();
+    assert!(toml["moved"].is_datetime());
+
+    let toml_str = "name = \"2024-01-01\"\n";
+    let json_data = Toml::new(toml_str.to_string()).unwrap().to_json().unwrap();
+    let toml = Toml::from_json(json_data).unwrap().get_toml();
+    assert_eq!(toml["name"].as_str(), Some("2024-01-01"));
+
+    let json_data = serde_json::json!({"tag": "1979-05-27T07:32:00Z"});
+    let toml = Toml::from_json(json_data).unwrap().get_toml();
+    assert_eq!(toml["tag"].as_str(), Some("1979-05-27T07:32:00Z"));
+}
+
+#[test]
+fn report_toml_errors() {
+    let error = Toml::new(String::from("[package]\nname = \n")).unwrap_err();
+    assert_eq!(error.exit_code(), 4);
+    assert_eq!(
+        error.in_file("Cargo.toml").to_string(),
+        "Cargo.toml:2:8: expected a value, found a newline"
+    );
+
+    let error = Toml::from_json(serde_json::json!({"a": null})).unwrap_err();
+    assert_eq!(error.exit_code(), 6);
+}
@@ -1,4 +1,5 @@
 use super::processor::Processor;
+use crate::Error;
 
 #[derive(Debug, Clone)]
 pub struct Yaml {
@@ -6,9 +7,12 @@ pub struct Yaml {
 }
 
 impl Yaml {
-    pub fn new(data: String) -> Self {
-        let data = serde_yaml::from_str(&data).unwrap();
-        Yaml { data }
+    pub fn new(data: String) -> Result<Self, Error> {
+        let data = serde_yaml::from_str(&data).map_err(|e| {
+            let position = e.location().map(|l| (l.line(), l.column()));
+            Error::decode("YAML", &e, &data, position)
+        })?;
+        Ok(Yaml { data })
     }
 
     #[allow(dead_code)]
@@ -20,17 +24,24 @@ impl Yaml {
 impl Processor for Yaml {
     type T = Yaml;
 
-    fn from_json(json_data: serde_json::Value) -> Self::T {
-        let data = serde_yaml::to_string(&json_data).unwrap();
-        Yaml::new(data)
+    fn from_json(json_data: serde_json::Value) -> Result<Self::T, Error> {
+        let data = serde_yaml::to_value(&json_data).map_err(encode_error)?;
+        Ok(Yaml { data })
     }
 
-    fn to_json(&self) -> serde_json::Value {
-        serde_json::to_value(self.data.clone()).unwrap()
+    fn to_json(&self) -> Result<serde_json::Value, Error> {
+        serde_json::to_value(self.data.clone()).map_err(|e| Error::decode("YAML", e, "", None))
     }
 
-    fn to_string(&self) -> String {
-        serde_yaml::to_string(&self.data).unwrap()
+    fn to_string(&self) -> Result<String, Error> {
+        serde_yaml::to_string(&self.data).map_err(encode_error)
+    }
+}
+
+fn encode_error(e: impl std::fmt::Display) -> Error {
+    Error::Encode {
+        language: String::from("YAML"),
+        message: e.to_string(),
     }
 }
 
@@ -47,7 +58,7 @@ fn convert_json_to_yaml() {
         }"#;
     let json_marshalled_val = serde_json::from_str(json_data).unwrap();
 
-    let yaml = Yaml::from_json(json_marshalled_val).get_yaml();
+    let yaml = Yaml::from_json(json_marshalled_val).unwrap().get_yaml();
 
     let yaml_val: serde_yaml::Value = serde_yaml::from_str(
         r#"
@@ -67,8 +78,8 @@ name: "John Doe"
 phones: ["+44 1234567", "+44 2345678"]
 "#;
 
-    let yaml = Yaml::new(yaml_str.to_string());
-    let json_data = yaml.to_json();
+    let yaml = Yaml::new(yaml_str.to_string()).unwrap();
+    let json_data = yaml.to_json().unwrap();
 
     let json_val: serde_json::Value = serde_json::from_str(
         r#"
@@ -84,3 +95,19 @@ phones: ["+44 1234567", "+44 2345678"]
     .unwrap();
     assert_eq!(json_data, json_val)
 }
+
+#[test]
+fn report_yaml_errors() {
+    let error = Yaml::new(String::from("a: 1\nb:\n  c: d: e\n")).unwrap_err();
+    assert_eq!(error.exit_code(), 4);
+    assert_eq!(
+        error.in_file("deploy.yaml").to_string(),
+        "deploy.yaml:3:7: mapping values are not allowed in this context"
+    );
+
+    let error = Yaml::new(String::from("? [1, 2]\n: 3"))
+        .unwrap()
+        .to_json()
+        .unwrap_err();
+    assert_eq!(error.exit_code(), 4);
+}